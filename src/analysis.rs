@@ -0,0 +1,150 @@
+use crate::data::Data;
+use crate::process::gain_to_db;
+use crate::wav::Wav;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Levels of a single channel, see [`Data::analyze`]
+///
+/// Levels are in dBFS, relative to a full scale square wave. Silence has a level of
+/// [`f64::NEG_INFINITY`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ChannelStats {
+    /// Highest absolute sample value
+    pub peak: f64,
+    /// Root mean square level
+    pub rms: f64,
+    /// Level of the mean sample value
+    pub dc_offset: f64,
+    /// Number of samples at the lowest or highest possible value
+    pub clipped_samples: usize,
+    /// Difference between the peak and RMS levels in dB, `0.0` for silence
+    pub crest_factor: f64,
+}
+
+/// Running sums for a single channel
+#[derive(Clone, Copy, Default)]
+struct Accumulator {
+    peak: u32,
+    sum: i64,
+    sum_of_squares: f64,
+    clipped_samples: usize,
+    num_samples: usize,
+}
+
+impl Data {
+    /// Levels of every channel in a single pass over the interleaved samples.
+    ///
+    /// ```
+    /// use wavv::Data;
+    ///
+    /// let data = Data::BitDepth16(vec![16384, 0, -16384, 0]);
+    /// let stats = data.analyze(2);
+    ///
+    /// assert!((stats[0].peak + 6.0206).abs() < 0.001);
+    /// assert!((stats[0].rms + 6.0206).abs() < 0.001);
+    /// assert_eq!(stats[0].crest_factor, 0.0);
+    /// assert_eq!(stats[1].peak, f64::NEG_INFINITY);
+    /// ```
+    pub fn analyze(&self, num_channels: usize) -> Vec<ChannelStats> {
+        let bit_depth = self.bit_depth();
+
+        match self {
+            Data::BitDepth8(s) => {
+                analyze(s.iter().map(|s| *s as i32 - 128), num_channels, bit_depth)
+            }
+            Data::BitDepth16(s) => analyze(s.iter().map(|s| *s as i32), num_channels, bit_depth),
+            Data::BitDepth24(s) => analyze(s.iter().copied(), num_channels, bit_depth),
+        }
+    }
+}
+
+impl Wav {
+    /// Levels of every channel, see [`Data::analyze`].
+    pub fn analyze(&self) -> Vec<ChannelStats> {
+        self.data.analyze(self.fmt.num_channels as usize)
+    }
+}
+
+fn analyze<I: Iterator<Item = i32>>(
+    samples: I,
+    num_channels: usize,
+    bit_depth: u16,
+) -> Vec<ChannelStats> {
+    let full_scale = 1_i32 << (bit_depth - 1);
+    let mut channels = vec![Accumulator::default(); num_channels];
+
+    for (sample, channel) in samples.zip((0..num_channels).cycle()) {
+        let channel = &mut channels[channel];
+
+        channel.peak = channel.peak.max(sample.unsigned_abs());
+        channel.sum += sample as i64;
+        channel.sum_of_squares += sample as f64 * sample as f64;
+        channel.num_samples += 1;
+
+        if sample == -full_scale || sample == full_scale - 1 {
+            channel.clipped_samples += 1;
+        }
+    }
+
+    let full_scale = full_scale as f64;
+
+    channels
+        .iter()
+        .map(|c| {
+            let num_samples = c.num_samples.max(1) as f64;
+            let peak = gain_to_db(c.peak as f64 / full_scale);
+            let rms = gain_to_db(libm::sqrt(c.sum_of_squares / num_samples) / full_scale);
+            let dc_offset = gain_to_db((c.sum as f64 / num_samples).abs() / full_scale);
+
+            ChannelStats {
+                peak,
+                rms,
+                dc_offset,
+                clipped_samples: c.clipped_samples,
+                crest_factor: if c.peak == 0 { 0.0 } else { peak - rms },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn analyze_sine() {
+        let samples = (0..4800)
+            .map(|i| {
+                let phase = i as f64 / 48.0 * 2.0 * core::f64::consts::PI;
+                (libm::sin(phase) * 0.5 * 8_388_608.0) as i32 + 4096
+            })
+            .collect();
+
+        let stats = Data::BitDepth24(samples).analyze(1);
+
+        assert_close(stats[0].peak, -6.02);
+        assert_close(stats[0].rms, -9.03);
+        assert_close(stats[0].crest_factor, 3.01);
+        assert_close(stats[0].dc_offset, -66.23);
+        assert_eq!(stats[0].clipped_samples, 0);
+    }
+
+    #[test]
+    fn count_clipped_samples() {
+        let wav = Wav::from_data(Data::BitDepth8(vec![0, 255, 255, 128, 0, 1]), 48_000, 3);
+        let stats = wav.analyze();
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].clipped_samples, 1);
+        assert_eq!(stats[1].clipped_samples, 2);
+        assert_eq!(stats[2].clipped_samples, 1);
+        assert_eq!(stats[0].peak, 0.0);
+        assert_close(stats[0].dc_offset, -6.02);
+        assert_eq!(stats[2].dc_offset, f64::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,385 @@
+use crate::chunk::{Chunk, ChunkTag};
+use crate::data::Data;
+use crate::error::Error;
+use crate::wav::Wav;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str;
+
+/// Size of the description at the start of the `bext` chunk
+const BEXT_DESCRIPTION_SIZE: usize = 256;
+
+impl Wav {
+    /// Split into one mono [`Wav`] per channel.
+    ///
+    /// Track names from the `bext` description (`sTRK1=...`) and the iXML `TRACK_LIST` are
+    /// carried over to the matching file, as is the speaker position of the channel mask.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![1, 2, 3, 4]), 48_000, 2);
+    /// let mono = wav.split_channels();
+    ///
+    /// assert_eq!(mono[0].data, Data::BitDepth16(vec![1, 3]));
+    /// assert_eq!(mono[1].data, Data::BitDepth16(vec![2, 4]));
+    /// ```
+    pub fn split_channels(&self) -> Vec<Wav> {
+        let num_channels = self.fmt.num_channels as usize;
+        let names = track_names(&self.chunks, num_channels);
+        let speakers = self.fmt.channel_mask().map(speakers);
+
+        (0..num_channels)
+            .map(|channel| {
+                let mut fmt = self.fmt.clone();
+                fmt.num_channels = 1;
+
+                let speaker = speakers.as_ref().and_then(|s| s.get(channel).copied());
+                fmt.set_channel_mask(speaker);
+
+                let mut chunks = self.chunks.clone();
+                set_track_names(&mut chunks, &names[channel..channel + 1]);
+
+                Wav {
+                    fmt,
+                    data: self.data.channel(channel, num_channels),
+                    chunks,
+                }
+            })
+            .collect()
+    }
+
+    /// Interleave the channels of several files of the same length into one file.
+    ///
+    /// Every file needs the same sample rate and number of frames, together they can't have
+    /// more than 65535 channels. Samples are converted to the bit depth of the first file whose
+    /// other chunks are kept. Track names are combined, the channel mask is combined when every
+    /// file has speaker positions in channel order.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let mut left = Wav::from_data(Data::BitDepth16(vec![1, 3]), 48_000, 1);
+    /// let mut right = Wav::from_data(Data::BitDepth16(vec![2, 4]), 48_000, 1);
+    ///
+    /// left.fmt.set_channel_mask(Some(0x1));
+    /// right.fmt.set_channel_mask(Some(0x2));
+    ///
+    /// let stereo = Wav::merge_channels(&[left, right]).unwrap();
+    ///
+    /// assert_eq!(stereo.data, Data::BitDepth16(vec![1, 2, 3, 4]));
+    /// assert_eq!(stereo.fmt.channel_mask(), Some(0x3));
+    /// ```
+    pub fn merge_channels(wavs: &[Wav]) -> Result<Wav, Error> {
+        let first = wavs.first().ok_or(Error::IncompatibleData)?;
+        let num_frames = first.num_frames();
+
+        if wavs.iter().any(|w| {
+            w.fmt.sample_rate != first.fmt.sample_rate
+                || w.fmt.num_channels == 0
+                || w.num_frames() != num_frames
+        }) {
+            return Err(Error::IncompatibleData);
+        }
+
+        let inputs: Vec<(usize, Vec<i32>)> = wavs
+            .iter()
+            .map(|w| (w.fmt.num_channels as usize, w.data.to_i32_samples()))
+            .collect();
+
+        let num_channels: usize = inputs.iter().map(|(n, _)| n).sum();
+
+        if num_channels > u16::MAX as usize {
+            return Err(Error::IncompatibleData);
+        }
+
+        let mut samples = Vec::with_capacity(num_frames * num_channels);
+
+        for frame in 0..num_frames {
+            for (n, input) in &inputs {
+                samples.extend_from_slice(&input[frame * n..(frame + 1) * n]);
+            }
+        }
+
+        let names: Vec<Option<String>> = wavs
+            .iter()
+            .flat_map(|w| track_names(&w.chunks, w.fmt.num_channels as usize))
+            .collect();
+
+        let mut fmt = first.fmt.clone();
+        fmt.num_channels = num_channels as u16;
+        fmt.set_channel_mask(merge_channel_masks(wavs));
+
+        let mut chunks = first.chunks.clone();
+        set_track_names(&mut chunks, &names);
+
+        Ok(Wav {
+            fmt,
+            data: Data::from_i32_samples(samples, first.data.bit_depth())?,
+            chunks,
+        })
+    }
+}
+
+/// Mask of every speaker, in channel order.
+fn speakers(channel_mask: u32) -> Vec<u32> {
+    (0..32)
+        .map(|bit| 1 << bit)
+        .filter(|speaker| channel_mask & speaker != 0)
+        .collect()
+}
+
+/// Combined channel mask, if every file has a speaker for each channel and the speakers stay
+/// in channel order.
+fn merge_channel_masks(wavs: &[Wav]) -> Option<u32> {
+    let mut merged = 0_u32;
+
+    for wav in wavs {
+        let mask = wav.fmt.channel_mask()?;
+
+        if mask == 0
+            || mask.count_ones() != wav.fmt.num_channels as u32
+            || mask.trailing_zeros() < 32 - merged.leading_zeros()
+        {
+            return None;
+        }
+
+        merged |= mask;
+    }
+
+    Some(merged)
+}
+
+fn find_text(chunks: &[Chunk], tag: &[u8; 4]) -> Option<(usize, String)> {
+    let index = chunks
+        .iter()
+        .position(|c| c.id == ChunkTag::from_bytes(tag))?;
+    let bytes = &chunks[index].bytes;
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+
+    str::from_utf8(&bytes[..end])
+        .ok()
+        .map(|text| (index, String::from(text)))
+}
+
+/// Contents of the first `<name>` element in `xml`.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+
+    Some(&xml[start..end])
+}
+
+/// Name of every channel, the iXML `TRACK_LIST` takes precedence over the `bext` description.
+fn track_names(chunks: &[Chunk], num_channels: usize) -> Vec<Option<String>> {
+    let mut names = vec![None; num_channels];
+
+    let mut set_name = |index: Option<usize>, name: &str| {
+        if let Some(slot) = index
+            .and_then(|i| i.checked_sub(1))
+            .and_then(|i| names.get_mut(i))
+        {
+            *slot = Some(String::from(name));
+        }
+    };
+
+    if let Some((_, description)) = find_text(chunks, b"bext") {
+        let description = description
+            .get(..BEXT_DESCRIPTION_SIZE)
+            .unwrap_or(&description);
+
+        for line in description.lines() {
+            if let Some((index, name)) = line.strip_prefix("sTRK").and_then(|l| l.split_once('=')) {
+                set_name(index.parse().ok(), name.trim_end());
+            }
+        }
+    }
+
+    if let Some((_, ixml)) = find_text(chunks, b"iXML") {
+        let mut tracks = element(&ixml, "TRACK_LIST").unwrap_or("");
+
+        while let Some(track) = element(tracks, "TRACK") {
+            if let Some(name) = element(track, "NAME") {
+                let index = element(track, "INTERLEAVE_INDEX").and_then(|i| i.trim().parse().ok());
+                set_name(index, name);
+            }
+
+            let end = tracks.find("</TRACK>").map_or(tracks.len(), |i| i + 8);
+            tracks = &tracks[end..];
+        }
+    }
+
+    names
+}
+
+/// Write the track names to existing `bext` and iXML chunks.
+fn set_track_names(chunks: &mut [Chunk], names: &[Option<String>]) {
+    if names.iter().all(Option::is_none) {
+        return;
+    }
+
+    if let Some((index, text)) = find_text(chunks, b"bext") {
+        let bytes = &mut chunks[index].bytes;
+
+        if bytes.len() >= BEXT_DESCRIPTION_SIZE {
+            let mut description: String = text
+                .get(..BEXT_DESCRIPTION_SIZE)
+                .unwrap_or(&text)
+                .lines()
+                .filter(|l| !l.starts_with("sTRK"))
+                .map(|l| format!("{}\r\n", l))
+                .collect();
+
+            for (i, name) in names.iter().enumerate() {
+                if let Some(name) = name {
+                    description.push_str(&format!("sTRK{}={}\r\n", i + 1, name));
+                }
+            }
+
+            let mut description = description.into_bytes();
+            description.resize(BEXT_DESCRIPTION_SIZE, 0);
+            bytes[..BEXT_DESCRIPTION_SIZE].copy_from_slice(&description);
+        }
+    }
+
+    if let Some((index, ixml)) = find_text(chunks, b"iXML") {
+        let mut tracks = format!("<TRACK_LIST><TRACK_COUNT>{}</TRACK_COUNT>", names.len());
+
+        for (i, name) in names.iter().enumerate() {
+            tracks.push_str(&format!(
+                "<TRACK><CHANNEL_INDEX>{0}</CHANNEL_INDEX><INTERLEAVE_INDEX>{0}</INTERLEAVE_INDEX><NAME>{1}</NAME></TRACK>",
+                i + 1,
+                name.as_deref().unwrap_or("")
+            ));
+        }
+
+        tracks.push_str("</TRACK_LIST>");
+
+        let ixml = match (ixml.find("<TRACK_LIST>"), ixml.find("</TRACK_LIST>")) {
+            (Some(start), Some(end)) => {
+                format!("{}{}{}", &ixml[..start], tracks, &ixml[end + 13..])
+            }
+            _ => match ixml.find("</BWFXML>") {
+                Some(end) => format!("{}{}{}", &ixml[..end], tracks, &ixml[end..]),
+                None => ixml,
+            },
+        };
+
+        chunks[index].bytes = ixml.into_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bext(description: &str) -> Chunk {
+        let mut bytes = description.as_bytes().to_vec();
+        bytes.resize(602, 0);
+
+        Chunk {
+            id: ChunkTag::from_bytes(b"bext"),
+            bytes,
+        }
+    }
+
+    fn ixml(text: &str) -> Chunk {
+        Chunk {
+            id: ChunkTag::from_bytes(b"iXML"),
+            bytes: text.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn split_carries_track_names() {
+        let mut wav = Wav::from_data(Data::BitDepth24(vec![1, 2, 3, 4, 5, 6]), 48_000, 3);
+        wav.fmt.set_channel_mask(Some(0x7));
+        wav.chunks = vec![
+            bext("sSPEED=025.000-ND\r\nsTRK1=Boom\r\nsTRK2=Lav 1\r\n"),
+            ixml("<BWFXML><TRACK_LIST><TRACK_COUNT>3</TRACK_COUNT><TRACK><INTERLEAVE_INDEX>3</INTERLEAVE_INDEX><NAME>Lav 2</NAME></TRACK></TRACK_LIST></BWFXML>"),
+        ];
+
+        let mono = wav.split_channels();
+
+        assert_eq!(mono.len(), 3);
+        assert_eq!(mono[2].data, Data::BitDepth24(vec![3, 6]));
+        assert_eq!(mono[1].fmt.channel_mask(), Some(0x2));
+        assert_eq!(
+            track_names(&mono[0].chunks, 1),
+            [Some(String::from("Boom"))]
+        );
+        assert_eq!(
+            track_names(&mono[2].chunks, 1),
+            [Some(String::from("Lav 2"))]
+        );
+        assert!(mono[1].chunks[0]
+            .bytes
+            .starts_with(b"sSPEED=025.000-ND\r\nsTRK1=Lav 1\r\n\0"));
+        assert_eq!(mono[1].chunks[0].bytes.len(), 602);
+
+        let merged = Wav::merge_channels(&mono).unwrap();
+
+        assert_eq!(merged.data, wav.data);
+        assert_eq!(merged.fmt, wav.fmt);
+        assert_eq!(
+            track_names(&merged.chunks, 3),
+            [
+                Some(String::from("Boom")),
+                Some(String::from("Lav 1")),
+                Some(String::from("Lav 2"))
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_validates_inputs() {
+        let a = Wav::from_data(Data::BitDepth16(vec![1, 2]), 48_000, 1);
+        let b = Wav::from_data(Data::BitDepth8(vec![128, 129]), 48_000, 1);
+        let c = Wav::from_data(Data::BitDepth16(vec![1]), 48_000, 1);
+        let d = Wav::from_data(Data::BitDepth16(vec![1, 2]), 44_100, 1);
+
+        let merged = Wav::merge_channels(&[a.clone(), b]).unwrap();
+
+        assert_eq!(merged.data, Data::BitDepth16(vec![1, 0, 2, 256]));
+        assert_eq!(merged.fmt.channel_mask(), None);
+        assert_eq!(
+            Wav::merge_channels(&[a.clone(), c]).unwrap_err(),
+            Error::IncompatibleData
+        );
+        assert_eq!(
+            Wav::merge_channels(&[a, d]).unwrap_err(),
+            Error::IncompatibleData
+        );
+        assert_eq!(
+            Wav::merge_channels(&[]).unwrap_err(),
+            Error::IncompatibleData
+        );
+
+        let wide = Wav::from_data(Data::BitDepth16(vec![]), 48_000, 40_000);
+
+        assert_eq!(
+            Wav::merge_channels(&[wide.clone(), wide]).unwrap_err(),
+            Error::IncompatibleData
+        );
+    }
+
+    #[test]
+    fn channel_masks_in_channel_order() {
+        let mut left = Wav::from_data(Data::BitDepth16(vec![]), 48_000, 1);
+        let mut right = left.clone();
+
+        left.fmt.set_channel_mask(Some(0x1));
+        right.fmt.set_channel_mask(Some(0x2));
+
+        assert_eq!(
+            merge_channel_masks(&[left.clone(), right.clone()]),
+            Some(0x3)
+        );
+        assert_eq!(merge_channel_masks(&[right, left]), None);
+    }
+}
@@ -0,0 +1,451 @@
+use crate::chunk::{Chunk, ChunkTag};
+use crate::data::Data;
+use crate::loudness::BEXT_SIZE;
+use crate::wav::Wav;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Field of a coding history line holding the MD5 of the audio
+const CODING_HISTORY_MD5: &str = "MD5=";
+
+/// Per round shift amounts of MD5
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// MD5 round constants, the integer part of `abs(sin(i + 1)) * 2^32`
+const MD5_CONSTANTS: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Lookup table of the reflected CRC-32 (IEEE 802.3) polynomial
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+/// Checksums of the audio content, see [`Wav::checksum`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Checksum {
+    /// MD5 of every sample
+    pub md5: [u8; 16],
+    /// CRC-32 of every sample
+    pub crc32: u32,
+    /// MD5 of the samples of every channel, empty unless requested
+    pub channels: Vec<[u8; 16]>,
+}
+
+impl Checksum {
+    /// MD5 as 32 lowercase hex digits.
+    pub fn md5_hex(&self) -> String {
+        hex(&self.md5)
+    }
+
+    /// Returns true if the first MD5 found in `text` matches, e.g. the contents of an `.md5`
+    /// sidecar file as written by `md5sum`.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![]), 48_000, 2);
+    /// let checksum = wav.checksum(false);
+    ///
+    /// assert!(checksum.matches("D41D8CD98F00B204E9800998ECF8427E  take_1.wav\n"));
+    /// assert!(!checksum.matches("take_1.wav"));
+    /// ```
+    pub fn matches(&self, text: &str) -> bool {
+        find_md5(text) == Some(self.md5)
+    }
+}
+
+/// Streaming MD5 (RFC 1321)
+#[derive(Clone)]
+pub(crate) struct Md5 {
+    state: [u32; 4],
+    buffer: [u8; 64],
+    buffered: usize,
+    length: u64,
+}
+
+impl Md5 {
+    pub(crate) fn new() -> Self {
+        Md5 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: [0; 64],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len() as u64;
+
+        while !bytes.is_empty() {
+            let size = (64 - self.buffered).min(bytes.len());
+            self.buffer[self.buffered..self.buffered + size].copy_from_slice(&bytes[..size]);
+            self.buffered += size;
+            bytes = &bytes[size..];
+
+            if self.buffered == 64 {
+                let block = self.buffer;
+                self.compress(&block);
+                self.buffered = 0;
+            }
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 16] {
+        let length = self.length.wrapping_mul(8);
+
+        // Pad with a single bit and zeros until 8 bytes are left in the block
+        self.update(&[0x80]);
+
+        while self.buffered != 56 {
+            self.update(&[0]);
+        }
+
+        self.update(&length.to_le_bytes());
+
+        let mut digest = [0; 16];
+
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(&self.state) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut words = [0_u32; 16];
+
+        for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(MD5_CONSTANTS[i])
+                .wrapping_add(words[g]);
+
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(MD5_SHIFTS[i]));
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+/// Computes a [`Checksum`] from interleaved samples pushed in pieces of any size, see
+/// [`Wav::checksum`].
+pub(crate) struct ChecksumBuilder {
+    md5: Md5,
+    crc32: u32,
+    channels: Vec<Md5>,
+    channel: usize,
+}
+
+impl ChecksumBuilder {
+    pub(crate) fn new(num_channels: usize, per_channel: bool) -> Self {
+        let num_channels = if per_channel { num_channels } else { 0 };
+
+        ChecksumBuilder {
+            md5: Md5::new(),
+            crc32: 0xffff_ffff,
+            channels: vec![Md5::new(); num_channels],
+            channel: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, data: &Data) {
+        let bytes = data.to_chunk().bytes;
+
+        self.md5.update(&bytes);
+
+        for byte in &bytes {
+            self.crc32 =
+                (self.crc32 >> 8) ^ CRC32_TABLE[((self.crc32 ^ *byte as u32) & 0xff) as usize];
+        }
+
+        if !self.channels.is_empty() {
+            for sample in bytes.chunks_exact((data.bit_depth() / 8) as usize) {
+                self.channels[self.channel].update(sample);
+                self.channel = (self.channel + 1) % self.channels.len();
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> Checksum {
+        Checksum {
+            md5: self.md5.finish(),
+            crc32: !self.crc32,
+            channels: self.channels.into_iter().map(Md5::finish).collect(),
+        }
+    }
+}
+
+impl Wav {
+    /// Checksums of the samples, independent of every other chunk, optionally per channel.
+    ///
+    /// Samples are hashed in the layout of a PCM `data` chunk: little endian, interleaved,
+    /// unsigned for 8 bit and signed otherwise. Compressed files are hashed as the PCM they
+    /// decode to.
+    ///
+    /// ```
+    /// use wavv::{Chunk, ChunkTag, Data, Wav};
+    ///
+    /// let mut wav = Wav::from_data(Data::BitDepth16(vec![1, -1, 2, -2]), 48_000, 2);
+    /// let checksum = wav.checksum(true);
+    ///
+    /// wav.chunks.push(Chunk { id: ChunkTag::from_bytes(b"iXML"), bytes: b"<BWFXML/>".to_vec() });
+    ///
+    /// assert_eq!(wav.checksum(true), checksum);
+    /// assert_eq!(checksum.channels.len(), 2);
+    /// ```
+    pub fn checksum(&self, per_channel: bool) -> Checksum {
+        let mut builder = ChecksumBuilder::new(self.fmt.num_channels as usize, per_channel);
+        builder.push(&self.data);
+        builder.finish()
+    }
+
+    /// MD5 stored in the last `MD5=` field of the `bext` coding history, see
+    /// [`Wav::set_bext_md5`].
+    pub fn bext_md5(&self) -> Option<[u8; 16]> {
+        bext_md5(&self.chunks)
+    }
+
+    /// Store the MD5 of the samples in a line of the `bext` coding history, removing the
+    /// `MD5=` fields of earlier lines.
+    ///
+    /// A `bext` chunk is added when there is none.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let mut wav = Wav::from_data(Data::BitDepth24(vec![0, 1, 2]), 48_000, 1);
+    /// wav.set_bext_md5();
+    ///
+    /// assert_eq!(wav.bext_md5(), Some(wav.checksum(false).md5));
+    /// ```
+    pub fn set_bext_md5(&mut self) {
+        let md5 = self.checksum(false).md5;
+        let tag = ChunkTag::from_bytes(b"bext");
+
+        let index = match self.chunks.iter().position(|c| c.id == tag) {
+            Some(index) => index,
+            None => {
+                self.chunks.push(Chunk {
+                    id: tag,
+                    bytes: vec![0; BEXT_SIZE],
+                });
+
+                self.chunks.len() - 1
+            }
+        };
+
+        let bytes = &mut self.chunks[index].bytes;
+        bytes.resize(bytes.len().max(BEXT_SIZE), 0);
+
+        let history = String::from_utf8_lossy(&bytes[BEXT_SIZE..]);
+        // Remove earlier `MD5=` fields, along with lines that only held one
+        let mut history: String = history
+            .trim_end_matches('\0')
+            .lines()
+            .map(|l| {
+                l.split(',')
+                    .filter(|field| !field.trim().starts_with(CODING_HISTORY_MD5))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .filter(|l| !l.is_empty())
+            .map(|l| format!("{}\r\n", l))
+            .collect();
+
+        history.push_str(&format!("{}{}\r\n", CODING_HISTORY_MD5, hex(&md5)));
+
+        bytes.truncate(BEXT_SIZE);
+        bytes.extend_from_slice(history.as_bytes());
+    }
+}
+
+/// MD5 from the last `MD5=` field of the `bext` coding history, the most recent line.
+pub(crate) fn bext_md5(chunks: &[Chunk]) -> Option<[u8; 16]> {
+    let bext = chunks
+        .iter()
+        .find(|c| c.id == ChunkTag::from_bytes(b"bext"))?;
+    let history = String::from_utf8_lossy(bext.bytes.get(BEXT_SIZE..)?);
+
+    history
+        .lines()
+        .flat_map(|l| l.split(','))
+        .rev()
+        .find_map(|field| field.trim().strip_prefix(CODING_HISTORY_MD5))
+        .and_then(find_md5)
+}
+
+/// First run of exactly 32 hex digits in `text`.
+fn find_md5(text: &str) -> Option<[u8; 16]> {
+    let token = text
+        .split(|c: char| !c.is_ascii_hexdigit())
+        .find(|t| t.len() == 32)?;
+
+    let mut md5 = [0; 16];
+
+    for (byte, i) in md5.iter_mut().zip((0..32).step_by(2)) {
+        *byte = u8::from_str_radix(&token[i..i + 2], 16).ok()?;
+    }
+
+    Some(md5)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn md5(bytes: &[u8]) -> String {
+        let mut md5 = Md5::new();
+        md5.update(bytes);
+        hex(&md5.finish())
+    }
+
+    #[test]
+    fn md5_test_suite() {
+        // RFC 1321 appendix A.5
+        assert_eq!(md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            md5(b"abcdefghijklmnopqrstuvwxyz"),
+            "c3fcd3d76192e4007dfb496cca67e13b"
+        );
+        assert_eq!(
+            md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+
+    #[test]
+    fn pieces_and_channels() {
+        let samples: Vec<i32> = (0..999).map(|i| i * 1001 - 500_000).collect();
+        let wav = Wav::from_data(Data::BitDepth24(samples.clone()), 48_000, 3);
+
+        let mut builder = ChecksumBuilder::new(3, true);
+
+        for piece in samples.chunks(100) {
+            builder.push(&Data::BitDepth24(piece.to_vec()));
+        }
+
+        let checksum = wav.checksum(true);
+
+        assert_eq!(builder.finish(), checksum);
+        assert_eq!(
+            checksum.md5,
+            Wav::from_bytes(&wav.to_bytes())
+                .unwrap()
+                .checksum(false)
+                .md5
+        );
+
+        for channel in 0..3 {
+            let mut md5 = Md5::new();
+            md5.update(&wav.data.channel(channel, 3).to_chunk().bytes);
+
+            assert_eq!(checksum.channels[channel], md5.finish());
+        }
+    }
+
+    #[test]
+    fn crc32() {
+        // Check value of CRC-32
+        let wav = Wav::from_data(Data::BitDepth8(b"123456789".to_vec()), 8000, 1);
+
+        assert_eq!(wav.checksum(false).crc32, 0xcbf43926);
+    }
+
+    #[test]
+    fn bext_coding_history() {
+        let mut bytes = vec![0; BEXT_SIZE];
+        bytes.extend_from_slice(
+            b"A=PCM,F=48000,W=16,M=mono,MD5=00112233445566778899aabbccddeeff\r\n",
+        );
+
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![1, 2, 3]), 48_000, 1);
+        wav.chunks.push(Chunk {
+            id: ChunkTag::from_bytes(b"bext"),
+            bytes,
+        });
+
+        assert_eq!(
+            wav.bext_md5(),
+            Some([
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff
+            ])
+        );
+
+        wav.set_bext_md5();
+        wav.set_bext_md5();
+
+        assert_eq!(wav.bext_md5(), Some(wav.checksum(false).md5));
+
+        let history = String::from_utf8(wav.chunks[0].bytes[BEXT_SIZE..].to_vec()).unwrap();
+
+        assert_eq!(
+            history,
+            format!(
+                "A=PCM,F=48000,W=16,M=mono\r\nMD5={}\r\n",
+                wav.checksum(false).md5_hex()
+            )
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use crate::error::Error;
+use crate::parse::{Diagnostics, ParseWarningKind};
 use alloc::vec;
 use alloc::vec::Vec;
-use core::convert::TryInto;
+use core::fmt;
 
 /// RIFF chunks are tagged with 4 byte identifiers.
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -14,34 +15,72 @@ pub enum ChunkTag {
     Data,
     /// File identifier, should be located right after the RIFF tag and chunk size
     Wave,
+    /// Number of frames in the file, mandatory for compressed formats.
+    Fact,
+    /// Container of sub chunks, the first 4 bytes hold the list type.
+    List,
+    /// Filler reserving space, e.g. for the header to grow without moving the sample data.
+    Junk,
+    /// Filler used to align the following chunk.
+    Pad,
+    /// Filler used by some recorders to align the sample data.
+    Fllr,
     /// Unkown/unhandled chunk tag, useful for parsing [`Chunk`] bytes.
     Unknown([u8; 4]),
 }
 
 impl ChunkTag {
-    fn from_bytes(bytes: &[u8; 4]) -> Self {
+    /// Tag for the given 4 byte identifier.
+    pub fn from_bytes(bytes: &[u8; 4]) -> Self {
         match bytes {
             [b'R', b'I', b'F', b'F'] => ChunkTag::Riff,
             [b'f', b'm', b't', b' '] => ChunkTag::Fmt,
             [b'd', b'a', b't', b'a'] => ChunkTag::Data,
             [b'W', b'A', b'V', b'E'] => ChunkTag::Wave,
+            [b'f', b'a', b'c', b't'] => ChunkTag::Fact,
+            [b'L', b'I', b'S', b'T'] => ChunkTag::List,
+            [b'J', b'U', b'N', b'K'] => ChunkTag::Junk,
+            [b'P', b'A', b'D', b' '] => ChunkTag::Pad,
+            [b'F', b'L', b'L', b'R'] => ChunkTag::Fllr,
             _ => ChunkTag::Unknown(*bytes),
         }
     }
 
-    fn to_bytes(self) -> [u8; 4] {
+    /// 4 byte identifier of the tag.
+    pub fn to_bytes(self) -> [u8; 4] {
         match self {
             ChunkTag::Riff => [b'R', b'I', b'F', b'F'],
             ChunkTag::Fmt => [b'f', b'm', b't', b' '],
             ChunkTag::Data => [b'd', b'a', b't', b'a'],
             ChunkTag::Wave => [b'W', b'A', b'V', b'E'],
+            ChunkTag::Fact => [b'f', b'a', b'c', b't'],
+            ChunkTag::List => [b'L', b'I', b'S', b'T'],
+            ChunkTag::Junk => [b'J', b'U', b'N', b'K'],
+            ChunkTag::Pad => [b'P', b'A', b'D', b' '],
+            ChunkTag::Fllr => [b'F', b'L', b'L', b'R'],
             ChunkTag::Unknown(bytes) => bytes,
         }
     }
+
+    /// Filler chunks only reserve space, their contents are meaningless.
+    pub(crate) fn is_filler(self) -> bool {
+        matches!(self, ChunkTag::Junk | ChunkTag::Pad | ChunkTag::Fllr)
+    }
+}
+
+impl fmt::Display for ChunkTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Tags are ASCII, show anything else as escaped bytes
+        for byte in self.to_bytes().iter() {
+            write!(f, "{}", core::ascii::escape_default(*byte))?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Resource Interchange File Format (RIFF) tagged chunk.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Chunk {
     /// Chunk tag
     pub id: ChunkTag,
@@ -51,23 +90,49 @@ pub struct Chunk {
 
 impl Chunk {
     pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
-        let id = bytes[0..4]
-            .try_into()
-            .map_err(|_| Error::CantParseSliceInto)
-            .map(ChunkTag::from_bytes)?;
+        let header = bytes.get(0..8).ok_or(Error::CantParseSliceInto {
+            tag: ChunkTag::Unknown([0; 4]),
+            offset: 0,
+        })?;
 
-        let size = bytes[4..8]
-            .try_into()
-            .map_err(|_| Error::CantParseSliceInto)
-            .map(|b| u32::from_le_bytes(b))?;
+        let id = ChunkTag::from_bytes(&[header[0], header[1], header[2], header[3]]);
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
 
         let start = 8;
         let end = 8 + size as usize;
-        let bytes: Vec<u8> = bytes[start..end].to_vec();
+        let bytes: Vec<u8> = bytes
+            .get(start..end)
+            .ok_or(Error::CantParseSliceInto {
+                tag: id,
+                offset: bytes.len(),
+            })?
+            .to_vec();
 
         Ok(Chunk { id, bytes })
     }
 
+    /// Read a little endian `u16` at `offset` in the chunk bytes.
+    pub(crate) fn read_u16(&self, offset: usize) -> Result<u16, Error> {
+        self.bytes
+            .get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or(Error::CantParseSliceInto {
+                tag: self.id,
+                offset,
+            })
+    }
+
+    /// Read a little endian `u32` at `offset` in the chunk bytes.
+    pub(crate) fn read_u32(&self, offset: usize) -> Result<u32, Error> {
+        self.bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(Error::CantParseSliceInto {
+                tag: self.id,
+                offset,
+            })
+    }
+
     pub(crate) fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = vec![];
 
@@ -75,47 +140,154 @@ impl Chunk {
         bytes.extend_from_slice(&(self.bytes.len() as u32).to_le_bytes());
         bytes.extend_from_slice(&self.bytes);
 
+        // Odd sized chunks are followed by a padding byte that is not part of the size
+        if self.bytes.len() & 1 == 1 {
+            bytes.push(0);
+        }
+
         bytes
     }
 }
 
-pub fn parse_chunks(bytes: &[u8]) -> Result<Vec<Chunk>, Error> {
-    let mut chunks: Vec<Chunk> = vec![];
+/// Parse the chunks of a WAVE file along with the offset of every chunk header in `bytes`.
+pub(crate) fn parse_chunks_with_offsets(
+    bytes: &[u8],
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<(usize, Chunk)>, Error> {
+    let mut chunks = vec![];
 
-    let riff = Chunk::from_bytes(bytes)?;
-
-    if riff.id != ChunkTag::Riff {
+    if bytes.len() < 12 || bytes[0..4] != ChunkTag::Riff.to_bytes() {
         return Err(Error::NoRiffChunkFound);
     }
 
-    let tag: [u8; 4] = riff.bytes[0..4].try_into().unwrap();
-
-    if tag != ChunkTag::Wave.to_bytes() {
+    if bytes[8..12] != ChunkTag::Wave.to_bytes() {
         return Err(Error::NoWaveTagFound);
     }
 
-    let mut index = 4;
+    let declared = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    let actual = (bytes.len() - 8) as u32;
+
+    if declared != actual {
+        diagnostics.report(4, ParseWarningKind::RiffSizeMismatch { declared, actual })?;
+    }
+
+    // Streaming writers leave the size at 0 or 0xffffffff until the file is finalized
+    let declared_end = if declared == 0 || declared == u32::MAX {
+        bytes.len()
+    } else {
+        bytes.len().min(8 + declared as usize)
+    };
+
+    // Recovering also reads chunks that were appended without updating the RIFF size
+    let end = if diagnostics.recovers() {
+        bytes.len()
+    } else {
+        declared_end
+    };
+
+    let mut index = 12;
 
-    while index < riff.bytes.len() {
-        let chunk = Chunk::from_bytes(&riff.bytes[index..])?;
+    // Skip trailing bytes that can't hold another chunk header
+    while index + 8 <= end {
+        let chunk = match recover_data_chunk(&bytes[index..end], diagnostics) {
+            Some(chunk) => {
+                let declared = u32::from_le_bytes([
+                    bytes[index + 4],
+                    bytes[index + 5],
+                    bytes[index + 6],
+                    bytes[index + 7],
+                ]);
+                let actual = chunk.bytes.len() as u32;
+
+                diagnostics.report(
+                    index + 4,
+                    ParseWarningKind::DataSizeRepaired { declared, actual },
+                )?;
+
+                chunk
+            }
+            None => match Chunk::from_bytes(&bytes[index..end]) {
+                Ok(chunk) => chunk,
+                // Trailing bytes beyond the RIFF size aren't necessarily chunks
+                Err(_) if index >= declared_end => break,
+                Err(e) => return Err(e.offset_by(index)),
+            },
+        };
 
         // Chunks should always have an even number of bytes,
         // if it is odd there is an empty padding byte at the end
         let chunk_length = chunk.bytes.len();
-        let padding_byte = (chunk_length & 1) * 8;
+        let padding_byte = chunk_length & 1;
 
-        index += 8 + chunk_length + padding_byte;
+        chunks.push((index, chunk));
 
-        chunks.push(chunk);
+        index += 8 + chunk_length + padding_byte;
     }
 
     Ok(chunks)
 }
 
+/// Clamp a `data` chunk with an unknown or too large size to the bytes that are available.
+///
+/// A size of 0 is only unknown when the chunk is the last one, otherwise it is an empty chunk.
+/// Returns `None` when not recovering, or when the chunk doesn't need repairing.
+fn recover_data_chunk(bytes: &[u8], diagnostics: &Diagnostics) -> Option<Chunk> {
+    if !diagnostics.recovers() || bytes[0..4] != ChunkTag::Data.to_bytes() {
+        return None;
+    }
+
+    let size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    let available = bytes.len() - 8;
+    let unknown = match size {
+        0 => available > 0 && !starts_with_chunk(&bytes[8..]),
+        u32::MAX => true,
+        _ => size as usize > available,
+    };
+
+    if unknown {
+        Some(Chunk {
+            id: ChunkTag::Data,
+            bytes: bytes[8..].to_vec(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Whether `bytes` start with a chunk header with a printable tag and a size that fits.
+fn starts_with_chunk(bytes: &[u8]) -> bool {
+    match bytes.get(0..8) {
+        Some(header) => {
+            let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+            header[0..4]
+                .iter()
+                .all(|b| b.is_ascii_graphic() || *b == b' ')
+                && size as usize <= bytes.len() - 8
+        }
+        None => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::Error;
+    use crate::parse::ParseOptions;
+
+    fn parse_chunks(bytes: &[u8]) -> Result<Vec<Chunk>, Error> {
+        let mut diagnostics = Diagnostics::new(&ParseOptions::lenient());
+        let chunks = parse_chunks_with_offsets(bytes, &mut diagnostics)?;
+
+        Ok(chunks.into_iter().map(|(_, chunk)| chunk).collect())
+    }
+
+    fn recover_chunks(bytes: &[u8]) -> Result<Vec<Chunk>, Error> {
+        let mut diagnostics = Diagnostics::new(&ParseOptions::recover());
+        let chunks = parse_chunks_with_offsets(bytes, &mut diagnostics)?;
+
+        Ok(chunks.into_iter().map(|(_, chunk)| chunk).collect())
+    }
 
     #[test]
     fn should_parse_chunks() {
@@ -202,4 +374,80 @@ mod tests {
 
         assert_eq!(parse_chunks(&bytes).unwrap_err(), Error::NoWaveTagFound);
     }
+
+    #[test]
+    fn should_skip_padding_byte_of_odd_chunks() {
+        let bytes: [u8; 34] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x1a, 0x00, 0x00, 0x00, // chunk size
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x72, 0x6e, 0x64, 0x6d, // rndm
+            0x03, 0x00, 0x00, 0x00, // chunk size
+            0xaa, 0xaa, 0xaa, // ...
+            0x00, // padding byte
+            0x64, 0x61, 0x74, 0x61, // data
+            0x02, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x00, // sample 1
+        ];
+
+        let chunks = parse_chunks(&bytes).unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].id, ChunkTag::Data);
+        assert_eq!(chunks[1].bytes, [0x01, 0x00]);
+    }
+
+    #[test]
+    fn recover_chunks_beyond_riff_size() {
+        let bytes: [u8; 45] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x0c, 0x00, 0x00, 0x00, // chunk size, only covering the first chunk
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x72, 0x6e, 0x64, 0x6d, // rndm
+            0x00, 0x00, 0x00, 0x00, // chunk size
+            0x64, 0x61, 0x74, 0x61, // data
+            0x04, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x00, 0x02, 0x00, // samples 1 and 2
+            0x4c, 0x49, 0x53, 0x54, // LIST
+            0x10, 0x00, 0x00, 0x00, // chunk size, beyond the end of the file
+            0xaa, 0xaa, 0xaa, 0xaa, 0xaa, // ...
+        ];
+
+        assert_eq!(parse_chunks(&bytes).unwrap().len(), 1);
+
+        let chunks = recover_chunks(&bytes).unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].id, ChunkTag::Data);
+        assert_eq!(chunks[1].bytes, [0x01, 0x00, 0x02, 0x00]);
+    }
+
+    #[test]
+    fn recover_empty_data_chunk() {
+        let bytes: [u8; 32] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x18, 0x00, 0x00, 0x00, // chunk size
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x64, 0x61, 0x74, 0x61, // data
+            0x00, 0x00, 0x00, 0x00, // chunk size
+            0x4c, 0x49, 0x53, 0x54, // LIST
+            0x04, 0x00, 0x00, 0x00, // chunk size
+            0x49, 0x4e, 0x46, 0x4f, // INFO
+        ];
+
+        let chunks = recover_chunks(&bytes).unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].bytes.is_empty());
+        assert_eq!(chunks[1].id, ChunkTag::List);
+
+        // Without a following chunk the size is unknown
+        let mut bytes = bytes;
+        bytes[20..24].copy_from_slice(&[0xff, 0x7f, 0x00, 0x80]);
+
+        let chunks = recover_chunks(&bytes).unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].bytes, bytes[20..]);
+    }
 }
@@ -0,0 +1,202 @@
+use crate::data::Data;
+use crate::error::Error;
+use crate::process::gain_to_db;
+use crate::wav::Wav;
+use alloc::vec::Vec;
+
+/// Full scale of samples scaled to 24 bit
+const FULL_SCALE: f64 = (1 << 23) as f64;
+
+/// Differences between the samples of two files, see [`Wav::compare`]
+///
+/// Differences are relative to full scale, levels are in dBFS. Files without differences
+/// have levels of [`f64::NEG_INFINITY`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Comparison {
+    /// Both files have the same length and every sample is equal
+    pub identical: bool,
+    /// First frame where any channel differs, or the length of the shorter file when it
+    /// matches the start of the longer one
+    pub first_difference: Option<usize>,
+    /// Number of samples that differ, ignoring the frames only one of the files has
+    pub differing_samples: usize,
+    /// Largest absolute difference between two samples, from `0.0` up to `2.0`
+    pub max_difference: f64,
+    /// Level of the largest absolute difference
+    pub max_difference_db: f64,
+    /// Root mean square of the residual of a null test, see [`Wav::residual`]
+    pub residual_rms: f64,
+    /// Level of the root mean square of the residual
+    pub residual_rms_db: f64,
+}
+
+impl Wav {
+    /// Compare the samples with those of `other`, ignoring every other chunk.
+    ///
+    /// Both files need the same sample rate and number of channels. Samples of different bit
+    /// depths are compared at the higher bit depth, a 16 bit file equals its 24 bit
+    /// conversion. Only the frames both files have are compared sample by sample.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let a = Wav::from_data(Data::BitDepth16(vec![0, 256, 512, 768]), 48_000, 2);
+    /// let b = Wav::from_data(Data::BitDepth24(vec![0, 65_536, 131_072, 196_608]), 48_000, 2);
+    /// assert!(a.compare(&b).unwrap().identical);
+    ///
+    /// let c = Wav::from_data(Data::BitDepth16(vec![0, 256, 512, 769]), 48_000, 2);
+    /// let comparison = a.compare(&c).unwrap();
+    ///
+    /// assert!(!comparison.identical);
+    /// assert_eq!(comparison.first_difference, Some(1));
+    /// assert_eq!(comparison.differing_samples, 1);
+    /// assert!((comparison.max_difference_db + 90.3).abs() < 0.1);
+    /// ```
+    pub fn compare(&self, other: &Wav) -> Result<Comparison, Error> {
+        self.check_comparable(other)?;
+
+        let num_channels = (self.fmt.num_channels as usize).max(1);
+        let a = self.data.to_i32_samples();
+        let b = other.data.to_i32_samples();
+
+        let mut first_difference = None;
+        let mut differing_samples = 0;
+        let mut max_difference = 0;
+        let mut sum_of_squares = 0.0;
+
+        for (i, (a, b)) in a.iter().zip(&b).enumerate() {
+            let difference = (*a as i64 - *b as i64).unsigned_abs();
+
+            if difference != 0 {
+                first_difference = first_difference.or(Some(i / num_channels));
+                differing_samples += 1;
+                max_difference = max_difference.max(difference);
+            }
+
+            let difference = difference as f64 / FULL_SCALE;
+            sum_of_squares += difference * difference;
+        }
+
+        let compared = a.len().min(b.len());
+
+        if a.len() != b.len() {
+            first_difference = first_difference.or(Some(compared / num_channels));
+        }
+
+        let max_difference = max_difference as f64 / FULL_SCALE;
+        let residual_rms = match compared {
+            0 => 0.0,
+            n => libm::sqrt(sum_of_squares / n as f64),
+        };
+
+        Ok(Comparison {
+            identical: first_difference.is_none(),
+            first_difference,
+            differing_samples,
+            max_difference,
+            max_difference_db: gain_to_db(max_difference),
+            residual_rms,
+            residual_rms_db: gain_to_db(residual_rms),
+        })
+    }
+
+    /// Null test residual, the samples of `other` subtracted from these samples.
+    ///
+    /// The residual has the higher bit depth of both files and the length of the shorter one,
+    /// differences beyond full scale are clipped. See [`Wav::compare`] for the requirements.
+    pub fn residual(&self, other: &Wav) -> Result<Wav, Error> {
+        self.check_comparable(other)?;
+
+        let bit_depth = self.data.bit_depth().max(other.data.bit_depth());
+
+        let samples: Vec<i32> = self
+            .data
+            .to_i32_samples()
+            .iter()
+            .zip(&other.data.to_i32_samples())
+            .map(|(a, b)| a.saturating_sub(*b).clamp(-(1 << 23), (1 << 23) - 1))
+            .collect();
+
+        let mut residual = Wav::from_data(
+            Data::from_i32_samples(samples, bit_depth)?,
+            self.fmt.sample_rate as usize,
+            self.fmt.num_channels as usize,
+        );
+        residual.fmt.set_channel_mask(self.fmt.channel_mask());
+
+        Ok(residual)
+    }
+
+    fn check_comparable(&self, other: &Wav) -> Result<(), Error> {
+        if self.fmt.sample_rate != other.fmt.sample_rate
+            || self.fmt.num_channels != other.fmt.num_channels
+        {
+            return Err(Error::IncompatibleData);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn different_lengths() {
+        let a = Wav::from_data(Data::BitDepth8(vec![128, 129, 130, 131]), 8000, 2);
+        let b = Wav::from_data(Data::BitDepth8(vec![128, 129]), 8000, 2);
+
+        let comparison = a.compare(&b).unwrap();
+
+        assert!(!comparison.identical);
+        assert_eq!(comparison.first_difference, Some(1));
+        assert_eq!(comparison.differing_samples, 0);
+        assert_eq!(comparison.max_difference, 0.0);
+        assert_eq!(comparison.max_difference_db, f64::NEG_INFINITY);
+        assert_eq!(comparison.residual_rms, 0.0);
+        assert_eq!(comparison.residual_rms_db, f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn null_test() {
+        let a = Wav::from_data(
+            Data::BitDepth16(vec![16384, -16384, 16384, -16384]),
+            48_000,
+            1,
+        );
+        let b = Wav::from_data(Data::BitDepth24(vec![0, 0, 0, 4_194_304]), 48_000, 1);
+
+        let comparison = a.compare(&b).unwrap();
+
+        assert_eq!(comparison.first_difference, Some(0));
+        assert_eq!(comparison.differing_samples, 4);
+        assert_eq!(comparison.max_difference, 1.0);
+        assert_eq!(comparison.max_difference_db, 0.0);
+
+        // Three differences of half scale and one of full scale
+        let rms = libm::sqrt((3.0 * 0.25 + 1.0) / 4.0);
+        assert!((comparison.residual_rms - rms).abs() < 1e-12);
+        assert!((comparison.residual_rms_db - gain_to_db(rms)).abs() < 1e-9);
+
+        let residual = a.residual(&b).unwrap();
+
+        assert_eq!(residual.fmt.bit_depth, 24);
+        assert_eq!(
+            residual.data,
+            Data::BitDepth24(vec![4_194_304, -4_194_304, 4_194_304, -8_388_608])
+        );
+        assert!(residual.compare(&residual).unwrap().identical);
+    }
+
+    #[test]
+    fn incompatible_files() {
+        let a = Wav::from_data(Data::BitDepth16(vec![0; 4]), 48_000, 2);
+        let b = Wav::from_data(Data::BitDepth16(vec![0; 4]), 48_000, 1);
+        let c = Wav::from_data(Data::BitDepth16(vec![0; 4]), 44_100, 2);
+
+        assert_eq!(a.compare(&b).unwrap_err(), Error::IncompatibleData);
+        assert_eq!(a.residual(&c).unwrap_err(), Error::IncompatibleData);
+    }
+}
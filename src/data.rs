@@ -3,9 +3,10 @@ use crate::error::Error;
 use crate::fmt::Fmt;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::ops::Range;
 
 /// Enum to hold samples for different bit depths
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Data {
     /// 8 bit audio
     BitDepth8(Vec<u8>),
@@ -93,12 +94,186 @@ impl Data {
             Data::BitDepth24(s) => s.len(),
         }
     }
+
+    /// Bit depth of the samples.
+    pub fn bit_depth(&self) -> u16 {
+        match self {
+            Data::BitDepth8(_) => 8,
+            Data::BitDepth16(_) => 16,
+            Data::BitDepth24(_) => 24,
+        }
+    }
+
+    /// Returns `true` if there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Convert the samples to another bit depth of `8`, `16` or `24` bits.
+    ///
+    /// Samples are scaled to keep their level, converting to a lower bit depth truncates.
+    ///
+    /// ```
+    /// use wavv::Data;
+    ///
+    /// let data = Data::BitDepth16(vec![-32768, 256, 32767]);
+    ///
+    /// assert_eq!(
+    ///     data.to_bit_depth(8).unwrap(),
+    ///     Data::BitDepth8(vec![0, 129, 255])
+    /// );
+    /// assert_eq!(
+    ///     data.to_bit_depth(24).unwrap(),
+    ///     Data::BitDepth24(vec![-8388608, 65536, 8388352])
+    /// );
+    /// ```
+    pub fn to_bit_depth(&self, bit_depth: u16) -> Result<Data, Error> {
+        Data::from_i32_samples(self.to_i32_samples(), bit_depth)
+    }
+
+    /// Samples scaled to 24 bit, which holds every supported bit depth without loss.
+    pub(crate) fn to_i32_samples(&self) -> Vec<i32> {
+        match self {
+            Data::BitDepth8(s) => s.iter().map(|s| (*s as i32 - 128) << 16).collect(),
+            Data::BitDepth16(s) => s.iter().map(|s| (*s as i32) << 8).collect(),
+            Data::BitDepth24(s) => s.clone(),
+        }
+    }
+
+    /// Convert samples scaled to 24 bit to the given bit depth.
+    pub(crate) fn from_i32_samples(samples: Vec<i32>, bit_depth: u16) -> Result<Data, Error> {
+        match bit_depth {
+            8 => Ok(Data::BitDepth8(
+                samples.iter().map(|s| ((s >> 16) + 128) as u8).collect(),
+            )),
+            16 => Ok(Data::BitDepth16(
+                samples.iter().map(|s| (s >> 8) as i16).collect(),
+            )),
+            24 => Ok(Data::BitDepth24(samples)),
+            _ => Err(Error::UnsupportedBitDepth(bit_depth)),
+        }
+    }
+
+    /// Value of a full scale sample, samples range from `-full_scale` to `full_scale - 1`.
+    pub(crate) fn full_scale(&self) -> f64 {
+        (1_u32 << (self.bit_depth() - 1)) as f64
+    }
+
+    /// Samples scaled to the range `-1.0..1.0`.
+    pub(crate) fn to_f64_samples(&self) -> Vec<f64> {
+        let scale = self.full_scale();
+
+        self.to_i32_samples()
+            .iter()
+            .map(|s| (*s >> (24 - self.bit_depth())) as f64 / scale)
+            .collect()
+    }
+
+    /// Round samples in the range `-1.0..1.0` to the given bit depth, clipping samples beyond
+    /// full scale. Returns the samples along with the number of clipped samples.
+    pub(crate) fn from_f64_samples(
+        samples: &[f64],
+        bit_depth: u16,
+    ) -> Result<(Data, usize), Error> {
+        if bit_depth != 8 && bit_depth != 16 && bit_depth != 24 {
+            return Err(Error::UnsupportedBitDepth(bit_depth));
+        }
+
+        let scale = (1_u32 << (bit_depth - 1)) as f64;
+        let mut clipped = 0;
+
+        let samples = samples
+            .iter()
+            .map(|s| {
+                let s = libm::round(s * scale);
+
+                if s < -scale || s > scale - 1.0 {
+                    clipped += 1;
+                }
+
+                (s.clamp(-scale, scale - 1.0) as i32) << (24 - bit_depth)
+            })
+            .collect();
+
+        Data::from_i32_samples(samples, bit_depth).map(|data| (data, clipped))
+    }
+
+    /// Replace the samples in `range` with `with`, converted to the bit depth of `self`.
+    pub(crate) fn splice(&mut self, range: Range<usize>, with: &Data) -> Result<(), Error> {
+        let with = with.to_bit_depth(self.bit_depth())?;
+
+        match (self, with) {
+            (Data::BitDepth8(s), Data::BitDepth8(w)) => {
+                s.splice(range, w);
+            }
+            (Data::BitDepth16(s), Data::BitDepth16(w)) => {
+                s.splice(range, w);
+            }
+            (Data::BitDepth24(s), Data::BitDepth24(w)) => {
+                s.splice(range, w);
+            }
+            _ => return Err(Error::IncompatibleData),
+        }
+
+        Ok(())
+    }
+
+    /// Remove the samples in `range`.
+    pub(crate) fn remove(&mut self, range: Range<usize>) {
+        match self {
+            Data::BitDepth8(s) => {
+                s.drain(range);
+            }
+            Data::BitDepth16(s) => {
+                s.drain(range);
+            }
+            Data::BitDepth24(s) => {
+                s.drain(range);
+            }
+        }
+    }
+
+    /// Samples of a single channel from interleaved samples.
+    pub(crate) fn channel(&self, index: usize, num_channels: usize) -> Self {
+        let step = num_channels.max(1);
+
+        match self {
+            Data::BitDepth8(s) => {
+                Data::BitDepth8(s.iter().skip(index).step_by(step).copied().collect())
+            }
+            Data::BitDepth16(s) => {
+                Data::BitDepth16(s.iter().skip(index).step_by(step).copied().collect())
+            }
+            Data::BitDepth24(s) => {
+                Data::BitDepth24(s.iter().skip(index).step_by(step).copied().collect())
+            }
+        }
+    }
+
+    /// Copy of the samples in the given range.
+    pub(crate) fn slice(&self, range: Range<usize>) -> Self {
+        match self {
+            Data::BitDepth8(s) => Data::BitDepth8(s[range].to_vec()),
+            Data::BitDepth16(s) => Data::BitDepth16(s[range].to_vec()),
+            Data::BitDepth24(s) => Data::BitDepth24(s[range].to_vec()),
+        }
+    }
+
+    /// Convert samples of any bit depth to 16 bit samples.
+    pub(crate) fn to_i16_samples(&self) -> Vec<i16> {
+        match self {
+            Data::BitDepth8(s) => s.iter().map(|s| ((*s as i16) - 128) << 8).collect(),
+            Data::BitDepth16(s) => s.clone(),
+            Data::BitDepth24(s) => s.iter().map(|s| (s >> 8) as i16).collect(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     #![allow(overflowing_literals)]
     use super::*;
+    use crate::fmt::AudioFormat;
     use alloc::vec;
 
     #[test]
@@ -122,9 +297,11 @@ mod tests {
     #[test]
     fn from_8_bit() {
         let fmt = Fmt {
+            format: AudioFormat::Pcm,
             bit_depth: 8,
             sample_rate: 48_000,
             num_channels: 1,
+            extra: vec![],
         };
 
         let bytes = [
@@ -140,9 +317,11 @@ mod tests {
     #[test]
     fn from_16_bit() {
         let fmt = Fmt {
+            format: AudioFormat::Pcm,
             bit_depth: 16,
             sample_rate: 48_000,
             num_channels: 1,
+            extra: vec![],
         };
 
         let bytes = [
@@ -160,9 +339,11 @@ mod tests {
     #[test]
     fn from_24_bit() {
         let fmt = Fmt {
+            format: AudioFormat::Pcm,
             bit_depth: 24,
             sample_rate: 48_000,
             num_channels: 1,
+            extra: vec![],
         };
 
         let bytes = [
@@ -0,0 +1,428 @@
+use crate::chunk::{Chunk, ChunkTag};
+use crate::error::Error;
+use crate::riff::{List, Node};
+use crate::wav::Wav;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Size of a cue point in the `cue ` chunk
+const CUE_POINT_SIZE: usize = 24;
+/// Size of the fixed part of the `smpl` chunk
+const SAMPLER_HEADER_SIZE: usize = 36;
+/// Size of a loop in the `smpl` chunk
+const SAMPLE_LOOP_SIZE: usize = 24;
+
+impl Wav {
+    /// New [`Wav`] holding the given range of frames, clamped to the available frames.
+    ///
+    /// Markers in the `cue ` and `smpl` chunks are moved along, markers outside the range are
+    /// removed together with their labels. Truncated `cue ` and `smpl` chunks are kept as they
+    /// are.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16((0..8).collect()), 48_000, 2);
+    ///
+    /// assert_eq!(wav.slice(1..3).data, Data::BitDepth16(vec![2, 3, 4, 5]));
+    /// ```
+    pub fn slice(&self, frames: Range<usize>) -> Self {
+        let frames = self.clamp(frames);
+        let samples = self.fmt.frame_to_sample(frames.start)..self.fmt.frame_to_sample(frames.end);
+
+        let mut chunks = self.chunks.clone();
+
+        shift_markers(&mut chunks, |position| {
+            if frames.contains(&(position as usize)) {
+                Some(position - frames.start as u32)
+            } else {
+                None
+            }
+        });
+
+        Wav {
+            fmt: self.fmt.clone(),
+            data: self.data.slice(samples),
+            chunks,
+        }
+    }
+
+    /// Insert the frames of `other` in front of the given frame.
+    ///
+    /// `other` needs the same sample rate and number of channels, samples of a different bit
+    /// depth are converted. Markers after the insertion point are moved back, the chunks of
+    /// `other` aren't copied.
+    pub fn insert(&mut self, frame: usize, other: &Wav) -> Result<(), Error> {
+        if other.fmt.sample_rate != self.fmt.sample_rate
+            || other.fmt.num_channels != self.fmt.num_channels
+        {
+            return Err(Error::IncompatibleData);
+        }
+
+        let frame = frame.min(self.num_frames());
+        let sample = self.fmt.frame_to_sample(frame);
+        let inserted = other.num_frames();
+
+        self.data.splice(sample..sample, &other.data)?;
+
+        shift_markers(&mut self.chunks, |position| {
+            if position as usize >= frame {
+                // Markers moved beyond the range of the chunks are removed
+                let shifted = position as u64 + inserted as u64;
+
+                if shifted > u32::MAX as u64 {
+                    None
+                } else {
+                    Some(shifted as u32)
+                }
+            } else {
+                Some(position)
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Add the frames of `other` to the end, see [`Wav::insert`].
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let mut wav = Wav::from_data(Data::BitDepth16(vec![1, 2]), 48_000, 1);
+    ///
+    /// for other in &[
+    ///     Wav::from_data(Data::BitDepth16(vec![3]), 48_000, 1),
+    ///     Wav::from_data(Data::BitDepth24(vec![1024]), 48_000, 1),
+    /// ] {
+    ///     wav.append(other).unwrap();
+    /// }
+    ///
+    /// assert_eq!(wav.data, Data::BitDepth16(vec![1, 2, 3, 4]));
+    /// ```
+    pub fn append(&mut self, other: &Wav) -> Result<(), Error> {
+        self.insert(self.num_frames(), other)
+    }
+
+    /// Remove the given range of frames, clamped to the available frames.
+    ///
+    /// Markers after the range are moved forward, markers inside the range are removed
+    /// together with their labels.
+    pub fn delete(&mut self, frames: Range<usize>) {
+        let frames = self.clamp(frames);
+        let samples = self.fmt.frame_to_sample(frames.start)..self.fmt.frame_to_sample(frames.end);
+
+        self.data.remove(samples);
+
+        shift_markers(&mut self.chunks, |position| {
+            if frames.contains(&(position as usize)) {
+                None
+            } else if position as usize >= frames.end {
+                Some(position - frames.len() as u32)
+            } else {
+                Some(position)
+            }
+        });
+    }
+
+    fn clamp(&self, frames: Range<usize>) -> Range<usize> {
+        let end = frames.end.min(self.num_frames());
+
+        frames.start.min(end)..end
+    }
+}
+
+/// Move the markers in `cue ` and `smpl` chunks to the frame returned by `shift`, removing
+/// them when it returns `None`.
+///
+/// Chunks too short to hold the number of markers they declare can't be edited safely and are
+/// left unchanged.
+fn shift_markers<F: Fn(u32) -> Option<u32>>(chunks: &mut [Chunk], shift: F) {
+    let mut removed = Vec::new();
+
+    for chunk in chunks.iter_mut() {
+        match &chunk.id.to_bytes() {
+            b"cue " => shift_cue_points(chunk, &shift, &mut removed),
+            b"smpl" => shift_sample_loops(chunk, &shift),
+            _ => {}
+        }
+    }
+
+    if removed.is_empty() {
+        return;
+    }
+
+    // Labels, notes and regions refer to the cue point ID in their first 4 bytes
+    for chunk in chunks.iter_mut().filter(|c| c.id == ChunkTag::List) {
+        let mut list = match List::from_chunk(chunk) {
+            Ok(list) if list.list_type == ChunkTag::from_bytes(b"adtl") => list,
+            _ => continue,
+        };
+
+        list.children.retain(|node| match node {
+            Node::Chunk(c) => match c.read_u32(0) {
+                Ok(id) => !removed.contains(&id),
+                Err(_) => true,
+            },
+            Node::List(_) => true,
+        });
+
+        *chunk = list.to_chunk();
+    }
+}
+
+/// Shift the sample offset of every cue point, collecting the IDs of removed points.
+fn shift_cue_points<F: Fn(u32) -> Option<u32>>(
+    chunk: &mut Chunk,
+    shift: &F,
+    removed: &mut Vec<u32>,
+) {
+    let count = match chunk.read_u32(0) {
+        Ok(count) => count as usize,
+        Err(_) => return,
+    };
+
+    if chunk.bytes.len() < 4 + count * CUE_POINT_SIZE {
+        return;
+    }
+
+    let mut bytes = Vec::new();
+    let mut kept = 0_u32;
+
+    bytes.extend_from_slice(&[0, 0, 0, 0]); // number of cue points (written below)
+
+    for point in chunk.bytes[4..].chunks_exact(CUE_POINT_SIZE).take(count) {
+        let id = u32::from_le_bytes([point[0], point[1], point[2], point[3]]);
+        let position = u32::from_le_bytes([point[4], point[5], point[6], point[7]]);
+        let offset = u32::from_le_bytes([point[20], point[21], point[22], point[23]]);
+
+        match shift(offset) {
+            Some(shifted) => {
+                // Only the play order position of files without a playlist matches the offset
+                let position = if position == offset {
+                    shifted
+                } else {
+                    position
+                };
+
+                bytes.extend_from_slice(&point[0..4]);
+                bytes.extend_from_slice(&position.to_le_bytes());
+                bytes.extend_from_slice(&point[8..20]);
+                bytes.extend_from_slice(&shifted.to_le_bytes());
+                kept += 1;
+            }
+            None => removed.push(id),
+        }
+    }
+
+    bytes[0..4].copy_from_slice(&kept.to_le_bytes());
+    chunk.bytes = bytes;
+}
+
+/// Shift the start and end of every sample loop, removing loops with either end removed.
+fn shift_sample_loops<F: Fn(u32) -> Option<u32>>(chunk: &mut Chunk, shift: &F) {
+    let count = match chunk.read_u32(28) {
+        Ok(count) => count as usize,
+        Err(_) => return,
+    };
+
+    let loops_end = SAMPLER_HEADER_SIZE + count * SAMPLE_LOOP_SIZE;
+
+    if chunk.bytes.len() < loops_end {
+        return;
+    }
+
+    let mut bytes = chunk.bytes[..SAMPLER_HEADER_SIZE].to_vec();
+    let mut kept = 0_u32;
+
+    for sample_loop in chunk.bytes[SAMPLER_HEADER_SIZE..loops_end].chunks_exact(SAMPLE_LOOP_SIZE) {
+        let read = |i: usize| {
+            u32::from_le_bytes([
+                sample_loop[i],
+                sample_loop[i + 1],
+                sample_loop[i + 2],
+                sample_loop[i + 3],
+            ])
+        };
+
+        if let (Some(start), Some(end)) = (shift(read(8)), shift(read(12))) {
+            bytes.extend_from_slice(&sample_loop[0..8]);
+            bytes.extend_from_slice(&start.to_le_bytes());
+            bytes.extend_from_slice(&end.to_le_bytes());
+            bytes.extend_from_slice(&sample_loop[16..24]);
+            kept += 1;
+        }
+    }
+
+    // Sampler specific data follows the loops
+    bytes.extend_from_slice(&chunk.bytes[loops_end..]);
+    bytes[28..32].copy_from_slice(&kept.to_le_bytes());
+    chunk.bytes = bytes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Data;
+    use alloc::vec;
+
+    fn cue_chunk() -> Chunk {
+        Chunk {
+            id: ChunkTag::from_bytes(b"cue "),
+            bytes: vec![
+                0x02, 0x00, 0x00, 0x00, // number of cue points
+                0x01, 0x00, 0x00, 0x00, // id
+                0x01, 0x00, 0x00, 0x00, // position
+                0x64, 0x61, 0x74, 0x61, // data
+                0x00, 0x00, 0x00, 0x00, // chunk start
+                0x00, 0x00, 0x00, 0x00, // block start
+                0x01, 0x00, 0x00, 0x00, // sample offset
+                0x02, 0x00, 0x00, 0x00, // id
+                0x05, 0x00, 0x00, 0x00, // position
+                0x64, 0x61, 0x74, 0x61, // data
+                0x00, 0x00, 0x00, 0x00, // chunk start
+                0x00, 0x00, 0x00, 0x00, // block start
+                0x05, 0x00, 0x00, 0x00, // sample offset
+            ],
+        }
+    }
+
+    fn label_list() -> Chunk {
+        List {
+            list_type: ChunkTag::from_bytes(b"adtl"),
+            children: vec![
+                Node::Chunk(Chunk {
+                    id: ChunkTag::from_bytes(b"labl"),
+                    bytes: b"\x01\x00\x00\x00one\x00".to_vec(),
+                }),
+                Node::Chunk(Chunk {
+                    id: ChunkTag::from_bytes(b"labl"),
+                    bytes: b"\x02\x00\x00\x00two\x00".to_vec(),
+                }),
+            ],
+        }
+        .to_chunk()
+    }
+
+    fn sample_offsets(chunk: &Chunk) -> Vec<u32> {
+        chunk.bytes[4..]
+            .chunks_exact(CUE_POINT_SIZE)
+            .map(|p| u32::from_le_bytes([p[20], p[21], p[22], p[23]]))
+            .collect()
+    }
+
+    fn wav() -> Wav {
+        let mut wav = Wav::from_data(Data::BitDepth16((0..16).collect()), 48_000, 2);
+        wav.chunks = vec![cue_chunk(), label_list()];
+        wav
+    }
+
+    #[test]
+    fn slice_moves_markers() {
+        let slice = wav().slice(3..10);
+
+        assert_eq!(slice.data, Data::BitDepth16((6..16).collect()));
+        assert_eq!(sample_offsets(&slice.chunks[0]), [2]);
+        assert_eq!(slice.chunks[0].read_u32(0).unwrap(), 1);
+        assert_eq!(slice.chunks[0].read_u32(8).unwrap(), 2);
+
+        let labels = List::from_chunk(&slice.chunks[1]).unwrap();
+
+        assert_eq!(labels.children.len(), 1);
+        assert_eq!(labels.children[0].id(), ChunkTag::from_bytes(b"labl"));
+    }
+
+    #[test]
+    fn insert_and_delete_move_markers() {
+        let mut wav = wav();
+        let other = Wav::from_data(Data::BitDepth8(vec![128, 129, 130, 131]), 48_000, 2);
+
+        wav.insert(2, &other).unwrap();
+
+        assert_eq!(wav.num_frames(), 10);
+        assert_eq!(wav.data.len(), 20);
+        assert_eq!(sample_offsets(&wav.chunks[0]), [1, 7]);
+
+        wav.delete(0..3);
+
+        assert_eq!(
+            wav.data,
+            Data::BitDepth16(vec![512, 768, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])
+        );
+        assert_eq!(sample_offsets(&wav.chunks[0]), [4]);
+
+        let mono = Wav::from_data(Data::BitDepth16(vec![0]), 48_000, 1);
+
+        assert_eq!(wav.append(&mono).unwrap_err(), Error::IncompatibleData);
+    }
+
+    #[test]
+    fn move_sample_loops() {
+        let mut bytes = vec![0; SAMPLER_HEADER_SIZE + SAMPLE_LOOP_SIZE];
+        bytes[28] = 1; // num sample loops
+        bytes[44] = 4; // loop start
+        bytes[48] = 8; // loop end
+
+        let mut chunks = vec![Chunk {
+            id: ChunkTag::from_bytes(b"smpl"),
+            bytes,
+        }];
+
+        shift_markers(&mut chunks, |p| Some(p + 2));
+
+        assert_eq!(chunks[0].read_u32(44).unwrap(), 6);
+        assert_eq!(chunks[0].read_u32(48).unwrap(), 10);
+
+        shift_markers(&mut chunks, |p| if p < 8 { Some(p) } else { None });
+
+        assert_eq!(chunks[0].read_u32(28).unwrap(), 0);
+        assert_eq!(chunks[0].bytes.len(), SAMPLER_HEADER_SIZE);
+    }
+
+    #[test]
+    fn keep_truncated_marker_chunks() {
+        let mut smpl = vec![0; SAMPLER_HEADER_SIZE + SAMPLE_LOOP_SIZE];
+        smpl[28] = 2; // num sample loops, only one follows
+        smpl[44] = 4; // loop start
+
+        // Two cue points declared, the second one is cut short
+        let mut cue = cue_chunk();
+        cue.bytes.truncate(4 + CUE_POINT_SIZE + 10);
+
+        let chunks = vec![
+            Chunk {
+                id: ChunkTag::from_bytes(b"cue "),
+                bytes: vec![0x01, 0x00],
+            },
+            cue,
+            Chunk {
+                id: ChunkTag::from_bytes(b"smpl"),
+                bytes: smpl,
+            },
+        ];
+
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![0; 8]), 48_000, 1);
+        wav.chunks = chunks.clone();
+
+        wav.insert(0, &Wav::from_data(Data::BitDepth16(vec![1, 2]), 48_000, 1))
+            .unwrap();
+        wav.delete(0..4);
+
+        assert_eq!(wav.data.len(), 6);
+        assert_eq!(wav.chunks, chunks);
+        assert_eq!(wav.slice(0..100).chunks, chunks);
+    }
+
+    #[test]
+    fn remove_markers_moved_out_of_range() {
+        let mut cue = cue_chunk();
+        cue.bytes[24..28].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![0; 8]), 48_000, 1);
+        wav.chunks = vec![cue];
+
+        wav.insert(0, &Wav::from_data(Data::BitDepth16(vec![1, 2]), 48_000, 1))
+            .unwrap();
+
+        assert_eq!(sample_offsets(&wav.chunks[0]), [7]);
+    }
+}
@@ -1,14 +1,30 @@
 use crate::chunk::ChunkTag;
+use crate::parse::ParseWarning;
+use core::fmt;
 
 /// Error type for different parsing failures
-#[derive(Debug, PartialEq)]
+///
+/// Offsets are counted in bytes from the start of the parsed bytes, which is the start of the
+/// file for [`Wav::from_bytes`](crate::Wav::from_bytes).
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Unknown or unsupported Chunk ID
     UnknownChunkID([u8; 4]),
-    /// Failed parsing slice into specific bytes
-    CantParseSliceInto,
+    /// Failed parsing slice into specific bytes, the field lies beyond the end of the chunk
+    CantParseSliceInto {
+        /// Chunk the field belongs to
+        tag: ChunkTag,
+        /// Offset of the field
+        offset: usize,
+    },
     /// Failed parsing chunk with given tag
-    CantParseChunk(ChunkTag),
+    CantParseChunk {
+        /// Chunk that failed to parse
+        tag: ChunkTag,
+        /// Offset of the offending bytes
+        offset: usize,
+    },
     /// No WAVE tag found
     NoWaveTagFound,
     /// No riff chunk found
@@ -21,4 +37,200 @@ pub enum Error {
     UnsupportedBitDepth(u16),
     /// Unsupported format
     UnsupportedFormat(u16),
+    /// Inconsistent header or chunk found while parsing in strict mode
+    Inconsistent(ParseWarning),
+    /// Required chunk or list with the given tag not found
+    MissingChunk(ChunkTag),
+    /// RIFF file of an unexpected form type
+    UnsupportedFormType(ChunkTag),
+    /// Chunk with the given tag can't be used here, e.g. editing the `data` chunk as metadata
+    UnexpectedChunk(ChunkTag),
+    /// Samples don't match the bit depth or number of channels of the file
+    IncompatibleData,
+    /// Processing would clip the given number of samples
+    Clipped(usize),
+    /// Reading or writing a file failed
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// Move offsets relative to a chunk or slice to offsets relative to its parent.
+    pub(crate) fn offset_by(self, base: usize) -> Self {
+        match self {
+            Error::CantParseSliceInto { tag, offset } => Error::CantParseSliceInto {
+                tag,
+                offset: base + offset,
+            },
+            Error::CantParseChunk { tag, offset } => Error::CantParseChunk {
+                tag,
+                offset: base + offset,
+            },
+            Error::Inconsistent(mut warning) => {
+                warning.offset += base;
+                Error::Inconsistent(warning)
+            }
+            error => error,
+        }
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::UnknownChunkID(a), Error::UnknownChunkID(b)) => a == b,
+            (
+                Error::CantParseSliceInto { tag, offset },
+                Error::CantParseSliceInto {
+                    tag: other_tag,
+                    offset: other_offset,
+                },
+            )
+            | (
+                Error::CantParseChunk { tag, offset },
+                Error::CantParseChunk {
+                    tag: other_tag,
+                    offset: other_offset,
+                },
+            ) => tag == other_tag && offset == other_offset,
+            (Error::UnsupportedBitDepth(a), Error::UnsupportedBitDepth(b))
+            | (Error::UnsupportedFormat(a), Error::UnsupportedFormat(b)) => a == b,
+            (Error::Inconsistent(a), Error::Inconsistent(b)) => a == b,
+            (Error::Clipped(a), Error::Clipped(b)) => a == b,
+            (Error::MissingChunk(a), Error::MissingChunk(b))
+            | (Error::UnsupportedFormType(a), Error::UnsupportedFormType(b))
+            | (Error::UnexpectedChunk(a), Error::UnexpectedChunk(b)) => a == b,
+            // I/O errors can't be compared, their kinds can
+            #[cfg(feature = "std")]
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            (Error::NoWaveTagFound, Error::NoWaveTagFound)
+            | (Error::NoRiffChunkFound, Error::NoRiffChunkFound)
+            | (Error::NoDataChunkFound, Error::NoDataChunkFound)
+            | (Error::NoFmtChunkFound, Error::NoFmtChunkFound)
+            | (Error::IncompatibleData, Error::IncompatibleData) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownChunkID(id) => write!(f, "unknown chunk id {}", ChunkTag::Unknown(*id)),
+            Error::CantParseSliceInto { tag, offset } => write!(
+                f,
+                "can't read field of `{}` chunk at byte offset {}, not enough bytes",
+                tag, offset
+            ),
+            Error::CantParseChunk { tag, offset } => write!(
+                f,
+                "can't parse `{}` chunk, invalid bytes at byte offset {}",
+                tag, offset
+            ),
+            Error::NoWaveTagFound => write!(f, "no WAVE tag found, not a WAV file"),
+            Error::NoRiffChunkFound => write!(f, "no RIFF chunk found, not a RIFF file"),
+            Error::NoDataChunkFound => write!(f, "no `data` chunk found"),
+            Error::NoFmtChunkFound => write!(f, "no `fmt ` chunk found"),
+            Error::UnsupportedBitDepth(bit_depth) => {
+                write!(f, "unsupported bit depth of {} bits", bit_depth)
+            }
+            Error::UnsupportedFormat(format) => {
+                write!(f, "unsupported audio format {:#06x}", format)
+            }
+            Error::Inconsistent(warning) => write!(f, "inconsistent file: {}", warning),
+            Error::MissingChunk(tag) => write!(f, "no `{}` chunk found", tag),
+            Error::UnsupportedFormType(tag) => write!(f, "unsupported RIFF form type `{}`", tag),
+            Error::UnexpectedChunk(tag) => write!(f, "`{}` chunk can't be used here", tag),
+            Error::IncompatibleData => write!(f, "samples don't match the format of the file"),
+            Error::Clipped(samples) => write!(f, "processing would clip {} samples", samples),
+            #[cfg(feature = "std")]
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseWarningKind;
+    use alloc::string::ToString;
+
+    #[test]
+    fn display_errors() {
+        let error = Error::CantParseSliceInto {
+            tag: ChunkTag::Fmt,
+            offset: 34,
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "can't read field of `fmt ` chunk at byte offset 34, not enough bytes"
+        );
+
+        let error = Error::Inconsistent(ParseWarning {
+            offset: 4,
+            kind: ParseWarningKind::RiffSizeMismatch {
+                declared: 100,
+                actual: 36,
+            },
+        });
+
+        assert_eq!(
+            error.to_string(),
+            "inconsistent file: RIFF size of 100 bytes but 36 bytes follow (at byte offset 4)"
+        );
+
+        assert_eq!(
+            Error::UnsupportedFormat(3).to_string(),
+            "unsupported audio format 0x0003"
+        );
+    }
+
+    #[test]
+    fn offset_by() {
+        let error = Error::CantParseChunk {
+            tag: ChunkTag::Data,
+            offset: 8,
+        };
+
+        assert_eq!(
+            error.offset_by(36),
+            Error::CantParseChunk {
+                tag: ChunkTag::Data,
+                offset: 44
+            }
+        );
+        assert_eq!(Error::NoWaveTagFound.offset_by(4), Error::NoWaveTagFound);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wrap_io_errors() {
+        use std::error::Error as _;
+
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+
+        assert!(error.source().is_some());
+        assert_eq!(
+            error,
+            Error::Io(std::io::Error::from(std::io::ErrorKind::NotFound))
+        );
+    }
 }
@@ -0,0 +1,263 @@
+use crate::data::Data;
+use crate::error::Error;
+use crate::process::db_to_gain;
+use crate::wav::Wav;
+use core::f64::consts::PI;
+use core::time::Duration;
+
+/// Range of the logarithmic curve, it starts this far below full level
+const LOGARITHMIC_RANGE: f64 = 60.0;
+
+/// Shape of a fade, see [`Wav::fade_in`]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum FadeCurve {
+    /// Gain rises linearly, crossfades dip by 6 dB in the middle
+    Linear,
+    /// Quarter sine, crossfades of uncorrelated signals keep a constant level
+    #[default]
+    EqualPower,
+    /// Level rises linearly in dB, starting 60 dB below full level
+    Logarithmic,
+    /// Half cosine, starting and ending slowly
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Gain at `position` of a fade in, from `0.0` at the start to `1.0` at the end.
+    fn gain(self, position: f64) -> f64 {
+        match self {
+            FadeCurve::Linear => position,
+            FadeCurve::EqualPower => libm::sin(position * PI / 2.0),
+            FadeCurve::Logarithmic if position <= 0.0 => 0.0,
+            FadeCurve::Logarithmic => db_to_gain(LOGARITHMIC_RANGE * (position - 1.0)),
+            FadeCurve::SCurve => (1.0 - libm::cos(position * PI)) / 2.0,
+        }
+    }
+}
+
+impl Wav {
+    /// Fade in over the first `duration`, clamped to the length of the file.
+    ///
+    /// Files without channels can't be faded.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, FadeCurve, Wav};
+    ///
+    /// let mut wav = Wav::from_data(Data::BitDepth16(vec![1000; 6]), 4, 1);
+    /// wav.fade_in(Duration::from_secs(1), FadeCurve::Linear).unwrap();
+    ///
+    /// assert_eq!(wav.data, Data::BitDepth16(vec![0, 250, 500, 750, 1000, 1000]));
+    /// ```
+    pub fn fade_in(&mut self, duration: Duration, curve: FadeCurve) -> Result<(), Error> {
+        let frames = self.fmt.time_to_frame(duration).min(self.num_frames());
+
+        self.apply_fade(0, frames, |position| curve.gain(position))
+    }
+
+    /// Fade out over the last `duration`, clamped to the length of the file.
+    ///
+    /// The last frame is silent.
+    pub fn fade_out(&mut self, duration: Duration, curve: FadeCurve) -> Result<(), Error> {
+        let num_frames = self.num_frames();
+        let frames = self.fmt.time_to_frame(duration).min(num_frames);
+
+        // Mirror of the fade in, reaching 0 at the last frame
+        let step = 1.0 / frames as f64;
+        self.apply_fade(num_frames - frames, frames, |position| {
+            curve.gain((1.0 - position - step).max(0.0))
+        })
+    }
+
+    /// Join `other` to the end, overlapping both by `duration` with a crossfade.
+    ///
+    /// `other` needs the same sample rate and number of channels, of which there has to be at
+    /// least one. Samples are converted to the bit depth of this file whose other chunks are
+    /// kept. The overlap is clamped to the length of the shortest file.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, FadeCurve, Wav};
+    ///
+    /// let a = Wav::from_data(Data::BitDepth16(vec![1000; 4]), 4, 1);
+    /// let b = Wav::from_data(Data::BitDepth16(vec![-1000; 4]), 4, 1);
+    ///
+    /// let joined = a.crossfade(&b, Duration::from_millis(500), FadeCurve::Linear).unwrap();
+    ///
+    /// assert_eq!(joined.data, Data::BitDepth16(vec![1000, 1000, 333, -333, -1000, -1000]));
+    /// ```
+    pub fn crossfade(
+        &self,
+        other: &Wav,
+        duration: Duration,
+        curve: FadeCurve,
+    ) -> Result<Wav, Error> {
+        if other.fmt.sample_rate != self.fmt.sample_rate
+            || other.fmt.num_channels != self.fmt.num_channels
+            || self.fmt.num_channels == 0
+        {
+            return Err(Error::IncompatibleData);
+        }
+
+        let num_channels = self.fmt.num_channels as usize;
+        let frames = self
+            .fmt
+            .time_to_frame(duration)
+            .min(self.num_frames())
+            .min(other.num_frames());
+
+        let mut samples = self.data.to_f64_samples();
+        let other = other.data.to_f64_samples();
+        let start = samples.len() - frames * num_channels;
+
+        for (i, (s, o)) in samples[start..].iter_mut().zip(&other).enumerate() {
+            // The crossfade is centered, starting and ending just outside the overlap
+            let position = ((i / num_channels) as f64 + 1.0) / (frames as f64 + 1.0);
+
+            *s = *s * curve.gain(1.0 - position) + o * curve.gain(position);
+        }
+
+        samples.extend_from_slice(&other[frames * num_channels..]);
+
+        let (data, _) = Data::from_f64_samples(&samples, self.data.bit_depth())?;
+
+        Ok(Wav {
+            fmt: self.fmt.clone(),
+            data,
+            chunks: self.chunks.clone(),
+        })
+    }
+
+    /// Multiply `frames` frames from `start` by the gain at their position in the fade.
+    fn apply_fade<F: Fn(f64) -> f64>(
+        &mut self,
+        start: usize,
+        frames: usize,
+        gain: F,
+    ) -> Result<(), Error> {
+        let num_channels = self.fmt.num_channels as usize;
+
+        if num_channels == 0 {
+            return Err(Error::IncompatibleData);
+        }
+
+        let mut samples = self.data.to_f64_samples();
+        let range = self.fmt.frame_to_sample(start)..self.fmt.frame_to_sample(start + frames);
+
+        for (i, s) in samples[range].iter_mut().enumerate() {
+            *s *= gain((i / num_channels) as f64 / frames as f64);
+        }
+
+        let (data, _) = Data::from_f64_samples(&samples, self.data.bit_depth())?;
+        self.data = data;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn curves_start_silent_and_end_at_full_level() {
+        for curve in [
+            FadeCurve::Linear,
+            FadeCurve::EqualPower,
+            FadeCurve::Logarithmic,
+            FadeCurve::SCurve,
+        ] {
+            assert_eq!(curve.gain(0.0), 0.0);
+            assert!((curve.gain(1.0) - 1.0).abs() < 1e-12);
+        }
+
+        assert!((FadeCurve::EqualPower.gain(0.5) - 0.5f64.sqrt()).abs() < 1e-12);
+        assert!((FadeCurve::Logarithmic.gain(0.5) - libm::pow(10.0, -1.5)).abs() < 1e-12);
+        assert!((FadeCurve::SCurve.gain(0.5) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn fade_out_every_bit_depth() {
+        let mut wav = Wav::from_data(Data::BitDepth8(vec![228, 228, 228, 228, 228]), 4, 1);
+        wav.fade_out(Duration::from_secs(1), FadeCurve::Linear)
+            .unwrap();
+
+        assert_eq!(wav.data, Data::BitDepth8(vec![228, 203, 178, 153, 128]));
+
+        let mut wav = Wav::from_data(Data::BitDepth24(vec![-400_000; 8]), 4, 2);
+        wav.fade_out(Duration::from_millis(500), FadeCurve::SCurve)
+            .unwrap();
+
+        assert_eq!(
+            wav.data,
+            Data::BitDepth24(vec![
+                -400_000, -400_000, -400_000, -400_000, -200_000, -200_000, 0, 0
+            ])
+        );
+    }
+
+    #[test]
+    fn fade_longer_than_file() {
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![800, 800]), 48_000, 1);
+        wav.fade_in(Duration::from_secs(1), FadeCurve::Linear)
+            .unwrap();
+
+        assert_eq!(wav.data, Data::BitDepth16(vec![0, 400]));
+    }
+
+    #[test]
+    fn reject_files_without_channels() {
+        let mut a = Wav::from_data(Data::BitDepth16(vec![1000; 4]), 4, 0);
+        let b = Wav::from_data(Data::BitDepth16(vec![-1000; 4]), 4, 0);
+
+        assert_eq!(
+            a.crossfade(&b, Duration::from_millis(500), FadeCurve::Linear)
+                .unwrap_err(),
+            Error::IncompatibleData
+        );
+        assert_eq!(
+            a.fade_in(Duration::from_secs(1), FadeCurve::Linear)
+                .unwrap_err(),
+            Error::IncompatibleData
+        );
+        assert_eq!(
+            a.fade_out(Duration::from_secs(1), FadeCurve::Linear)
+                .unwrap_err(),
+            Error::IncompatibleData
+        );
+    }
+
+    #[test]
+    fn crossfade_converts_bit_depth() {
+        let a = Wav::from_data(Data::BitDepth16(vec![256, 256, 256, 256]), 2, 2);
+        let b = Wav::from_data(Data::BitDepth24(vec![0, 0, 65_536, 65_536]), 2, 2);
+
+        let joined = a
+            .crossfade(&b, Duration::from_millis(500), FadeCurve::Linear)
+            .unwrap();
+
+        assert_eq!(
+            joined.data,
+            Data::BitDepth16(vec![256, 256, 128, 128, 256, 256])
+        );
+    }
+
+    #[test]
+    fn crossfade_incompatible() {
+        let a = Wav::from_data(Data::BitDepth16(vec![0; 4]), 48_000, 2);
+        let b = Wav::from_data(Data::BitDepth16(vec![0; 4]), 48_000, 1);
+        let c = Wav::from_data(Data::BitDepth16(vec![0; 4]), 44_100, 2);
+
+        assert_eq!(
+            a.crossfade(&b, Duration::from_secs(1), FadeCurve::EqualPower)
+                .unwrap_err(),
+            Error::IncompatibleData
+        );
+        assert_eq!(
+            a.crossfade(&c, Duration::from_secs(1), FadeCurve::EqualPower)
+                .unwrap_err(),
+            Error::IncompatibleData
+        );
+    }
+}
@@ -0,0 +1,670 @@
+use crate::checksum::{bext_md5, Checksum, ChecksumBuilder};
+use crate::chunk::{Chunk, ChunkTag};
+use crate::data::Data;
+use crate::error::Error;
+use crate::fmt::{AudioFormat, Fmt};
+use crate::ima_adpcm;
+use crate::ms_adpcm;
+use crate::overview::{Overview, OverviewBuilder};
+use alloc::vec;
+use alloc::vec::Vec;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Size of the buffer used when moving sample data around in a file.
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// WAV file on disk whose metadata chunks can be edited without touching the sample data.
+///
+/// Only the chunk headers and the non-audio chunks are read when opening. On
+/// [`WavFile::commit`] the header region in front of the `data` chunk is rewritten, filler
+/// chunks (`JUNK`, `PAD `, `FLLR`) are reused to absorb size changes. Only when the new
+/// header doesn't fit is the sample data moved, using a streaming copy.
+///
+/// Chunks found after the `data` chunk are moved in front of it, so the sample data ends
+/// the file and can be appended to with [`WavFile::append`].
+///
+/// ```no_run
+/// use wavv::{Chunk, ChunkTag, WavFile};
+///
+/// let mut file = WavFile::open("recording.wav").unwrap();
+///
+/// file.set_chunk(Chunk {
+///     id: ChunkTag::from_bytes(b"iXML"),
+///     bytes: b"<BWFXML></BWFXML>".to_vec(),
+/// })
+/// .unwrap();
+///
+/// file.commit().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct WavFile {
+    file: File,
+    fmt: Fmt,
+    chunks: Vec<Chunk>,
+    data_offset: u64,
+    data_size: u64,
+    trailing: Option<ChunkTag>,
+}
+
+impl WavFile {
+    /// Open a WAV file for editing, reading everything but the sample data.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let file_size = file.metadata()?.len();
+
+        let mut header = [0; 12];
+        file.read_exact(&mut header)
+            .map_err(|_| Error::NoRiffChunkFound)?;
+
+        if header[0..4] != ChunkTag::Riff.to_bytes() {
+            return Err(Error::NoRiffChunkFound);
+        }
+
+        if header[8..12] != ChunkTag::Wave.to_bytes() {
+            return Err(Error::NoWaveTagFound);
+        }
+
+        let riff_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        let end = file_size.min(8 + riff_size);
+
+        let mut chunks = vec![];
+        let mut data = None;
+        let mut trailing = None;
+        let mut offset = 12;
+
+        while offset + 8 <= end {
+            let mut header = [0; 8];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut header)?;
+
+            let id = ChunkTag::from_bytes(&[header[0], header[1], header[2], header[3]]);
+            let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+
+            if offset + 8 + size > file_size {
+                return Err(Error::CantParseSliceInto {
+                    tag: id,
+                    offset: file_size as usize,
+                });
+            }
+
+            if data.is_some() && trailing.is_none() {
+                trailing = Some(id);
+            }
+
+            if id == ChunkTag::Data {
+                data = Some((offset, size));
+            } else if !id.is_filler() {
+                let mut bytes = vec![0; size as usize];
+                file.read_exact(&mut bytes)?;
+                chunks.push(Chunk { id, bytes });
+            }
+
+            offset += 8 + size + (size & 1);
+        }
+
+        let fmt = chunks
+            .iter()
+            .find(|c| c.id == ChunkTag::Fmt)
+            .ok_or(Error::NoFmtChunkFound)
+            .and_then(Fmt::from_chunk)?;
+
+        let (data_offset, data_size) = data.ok_or(Error::NoDataChunkFound)?;
+
+        Ok(WavFile {
+            file,
+            fmt,
+            chunks,
+            data_offset,
+            data_size,
+            trailing,
+        })
+    }
+
+    /// Format of the sample data
+    pub fn fmt(&self) -> &Fmt {
+        &self.fmt
+    }
+
+    /// Every chunk except for the `data` chunk and filler chunks, in the order they are written.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Find the first chunk with the given tag.
+    pub fn chunk(&self, id: ChunkTag) -> Option<&Chunk> {
+        self.chunks.iter().find(|c| c.id == id)
+    }
+
+    /// Replace the first chunk with the same tag, or add it in front of the sample data.
+    ///
+    /// The `fmt_`, `fact` and `data` chunks describe the sample data and can't be replaced.
+    pub fn set_chunk(&mut self, chunk: Chunk) -> Result<(), Error> {
+        check_editable(chunk.id)?;
+
+        match self.chunks.iter_mut().find(|c| c.id == chunk.id) {
+            Some(existing) => *existing = chunk,
+            None => self.chunks.push(chunk),
+        }
+
+        Ok(())
+    }
+
+    /// Remove the first chunk with the given tag.
+    pub fn remove_chunk(&mut self, id: ChunkTag) -> Result<Option<Chunk>, Error> {
+        check_editable(id)?;
+
+        let index = self.chunks.iter().position(|c| c.id == id);
+
+        Ok(index.map(|i| self.chunks.remove(i)))
+    }
+
+    /// Write the edited chunks to the file.
+    ///
+    /// Space left over in front of the sample data is filled with a `JUNK` chunk, when the
+    /// chunks don't fit the sample data is moved back.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let mut header = vec![
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x00, 0x00, 0x00, 0x00, // chunk size (written below)
+            0x57, 0x41, 0x56, 0x45, // WAVE
+        ];
+
+        for chunk in &self.chunks {
+            header.extend_from_slice(&chunk.to_bytes());
+        }
+
+        let needed = header.len() as u64;
+        let available = self.data_offset;
+
+        // A filler chunk needs at least its own header, and keeps the data at an even offset
+        let data_offset =
+            if needed == available || (needed + 8 <= available && available.is_multiple_of(2)) {
+                if needed < available {
+                    let junk = Chunk {
+                        id: ChunkTag::Junk,
+                        bytes: vec![0; (available - needed - 8) as usize],
+                    };
+
+                    header.extend_from_slice(&junk.to_bytes());
+                }
+
+                available
+            } else {
+                self.move_data(needed)?;
+                needed
+            };
+
+        let data_end = data_offset + 8 + self.data_size + (self.data_size & 1);
+        let riff_size = (data_end - 8) as u32;
+
+        header[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.set_len(data_end)?;
+        self.file.flush()?;
+
+        self.data_offset = data_offset;
+        self.trailing = None;
+
+        Ok(())
+    }
+
+    /// Number of frames in the `data` chunk.
+    pub fn num_frames(&self) -> u64 {
+        match self.fmt.block_align() {
+            0 => 0,
+            block_align => self.data_size / block_align as u64,
+        }
+    }
+
+    /// Levels of every block of `frames_per_block` frames, reading the sample data in pieces.
+    ///
+    /// Compressed files are decoded like [`Wav::from_bytes`](crate::Wav::from_bytes), see
+    /// [`OverviewBuilder`].
+    ///
+    /// ```no_run
+    /// use wavv::WavFile;
+    ///
+    /// let mut file = WavFile::open("recording.wav").unwrap();
+    /// let overview = file.overview(256).unwrap();
+    ///
+    /// std::fs::write("recording.dat", overview.to_audiowaveform(16).unwrap()).unwrap();
+    /// ```
+    pub fn overview(&mut self, frames_per_block: usize) -> Result<Overview, Error> {
+        let mut builder = OverviewBuilder::new(&self.fmt, frames_per_block);
+        self.read_samples(|data| builder.push(data))?;
+
+        Ok(builder.finish())
+    }
+
+    /// Checksums of the samples, reading the sample data in pieces, see
+    /// [`Wav::checksum`](crate::Wav::checksum).
+    ///
+    /// Compressed files are hashed as the PCM they decode to, like the parsed file.
+    ///
+    /// ```no_run
+    /// use wavv::WavFile;
+    ///
+    /// let mut file = WavFile::open("recording.wav").unwrap();
+    /// let checksum = file.checksum(false).unwrap();
+    ///
+    /// assert_eq!(file.bext_md5(), Some(checksum.md5));
+    /// ```
+    pub fn checksum(&mut self, per_channel: bool) -> Result<Checksum, Error> {
+        let mut builder = ChecksumBuilder::new(self.fmt.num_channels as usize, per_channel);
+        self.read_samples(|data| builder.push(data))?;
+
+        Ok(builder.finish())
+    }
+
+    /// MD5 stored in the `bext` coding history, see
+    /// [`Wav::bext_md5`](crate::Wav::bext_md5).
+    pub fn bext_md5(&self) -> Option<[u8; 16]> {
+        bext_md5(&self.chunks)
+    }
+
+    /// Append samples to the end of the `data` chunk, the existing samples aren't read.
+    ///
+    /// Samples need to match the bit depth of the file and hold whole frames, only PCM files
+    /// can be appended to. The sample data needs to be the last chunk in the file, a file with
+    /// chunks after it fails with [`Error::UnexpectedChunk`] until [`WavFile::commit`] moved
+    /// them in front. The RIFF and `data` sizes are updated right away.
+    ///
+    /// ```no_run
+    /// use wavv::{Data, WavFile};
+    ///
+    /// let mut file = WavFile::open("log.wav").unwrap();
+    ///
+    /// file.append(&Data::BitDepth16(vec![0; 4800])).unwrap();
+    /// ```
+    pub fn append(&mut self, data: &Data) -> Result<(), Error> {
+        let num_channels = self.fmt.num_channels as usize;
+
+        if self.fmt.format != AudioFormat::Pcm
+            || data.bit_depth() != self.fmt.bit_depth
+            || num_channels == 0
+            || !data.len().is_multiple_of(num_channels)
+        {
+            return Err(Error::IncompatibleData);
+        }
+
+        if let Some(tag) = self.trailing {
+            return Err(Error::UnexpectedChunk(tag));
+        }
+
+        let bytes = data.to_chunk().bytes;
+        let data_size = self.data_size + bytes.len() as u64;
+        let data_end = self.data_offset + 8 + data_size;
+
+        if data_end + (data_size & 1) - 8 > u32::MAX as u64 {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file would exceed the 4 GiB limit of RIFF",
+            )));
+        }
+
+        // Overwrites the padding byte of an odd sized chunk
+        self.file
+            .seek(SeekFrom::Start(self.data_offset + 8 + self.data_size))?;
+        self.file.write_all(&bytes)?;
+
+        if data_size & 1 == 1 {
+            self.file.write_all(&[0])?;
+        }
+
+        self.file.set_len(data_end + (data_size & 1))?;
+
+        let riff_size = (data_end + (data_size & 1) - 8) as u32;
+
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&riff_size.to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(self.data_offset + 4))?;
+        self.file.write_all(&(data_size as u32).to_le_bytes())?;
+        self.file.flush()?;
+
+        self.data_size = data_size;
+
+        Ok(())
+    }
+
+    /// Read the samples in pieces of whole frames, ADPCM is decoded in pieces of whole blocks.
+    fn read_samples<F: FnMut(&Data)>(&mut self, mut f: F) -> Result<(), Error> {
+        let num_channels = self.fmt.num_channels as usize;
+        let (block_align, len) = match self.fmt.format {
+            AudioFormat::Pcm => {
+                let block_align = self.fmt.block_align() as u64;
+                (block_align, self.num_frames() * block_align)
+            }
+            // The last block may be partial, MS ADPCM uses the declared block size like `Wav`
+            AudioFormat::MsAdpcm { .. } => {
+                let fmt = self.chunk(ChunkTag::Fmt).ok_or(Error::NoFmtChunkFound)?;
+                (fmt.read_u16(12)? as u64, self.data_size)
+            }
+            AudioFormat::ImaAdpcm { .. } => (self.fmt.block_align() as u64, self.data_size),
+        };
+        let block_align = block_align.max(1) as usize;
+
+        // Frames left according to the `fact` chunk, to drop the padding of the last block
+        let mut remaining = self
+            .chunk(ChunkTag::Fact)
+            .and_then(|c| c.read_u32(0).ok())
+            .map(|frames| frames as usize);
+
+        // Whole frames or blocks per read
+        let mut buffer = vec![0; (COPY_BUFFER_SIZE / block_align).max(1) * block_align];
+        let mut read = 0;
+
+        self.file.seek(SeekFrom::Start(self.data_offset + 8))?;
+
+        while read < len {
+            let size = (len - read).min(buffer.len() as u64) as usize;
+            self.file.read_exact(&mut buffer[..size])?;
+
+            let bytes = &buffer[..size];
+            let offset = (self.data_offset + 8 + read) as usize;
+
+            let data = match self.fmt.format {
+                AudioFormat::Pcm => Data::from_chunk(
+                    &self.fmt,
+                    &Chunk {
+                        id: ChunkTag::Data,
+                        bytes: bytes.to_vec(),
+                    },
+                )?,
+                AudioFormat::ImaAdpcm { samples_per_block } => Data::BitDepth16(
+                    ima_adpcm::decode(bytes, num_channels, samples_per_block as usize, remaining)
+                        .map_err(|e| e.offset_by(offset))?,
+                ),
+                AudioFormat::MsAdpcm { .. } => Data::BitDepth16(
+                    ms_adpcm::decode(bytes, num_channels, block_align, &self.fmt.extra, remaining)
+                        .map_err(|e| e.offset_by(offset))?,
+                ),
+            };
+
+            if let Some(remaining) = &mut remaining {
+                *remaining = remaining.saturating_sub(data.len() / num_channels.max(1));
+            }
+
+            f(&data);
+            read += size as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Move the `data` chunk, including its header, to a new offset.
+    fn move_data(&mut self, to: u64) -> Result<(), Error> {
+        let from = self.data_offset;
+        let len = 8 + self.data_size + (self.data_size & 1);
+        let mut buffer = vec![0; COPY_BUFFER_SIZE];
+
+        // Copy from the end when moving back so the source isn't overwritten before it's read
+        let mut copied = 0;
+
+        while copied < len {
+            let size = (len - copied).min(COPY_BUFFER_SIZE as u64);
+            let position = if to > from {
+                len - copied - size
+            } else {
+                copied
+            };
+
+            // The padding byte of the last chunk may be missing
+            let buffer = &mut buffer[..size as usize];
+            self.file.seek(SeekFrom::Start(from + position))?;
+            read_padded(&mut self.file, buffer)?;
+
+            self.file.seek(SeekFrom::Start(to + position))?;
+            self.file.write_all(buffer)?;
+
+            copied += size;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_editable(id: ChunkTag) -> Result<(), Error> {
+    match id {
+        ChunkTag::Fmt | ChunkTag::Fact | ChunkTag::Data | ChunkTag::Riff | ChunkTag::Wave => {
+            Err(Error::UnexpectedChunk(id))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read as many bytes as available, filling the rest of the buffer with zeros.
+fn read_padded(file: &mut File, buffer: &mut [u8]) -> Result<(), Error> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    for byte in &mut buffer[read..] {
+        *byte = 0;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseOptions;
+    use crate::wav::Wav;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("wavv_{}_{}.wav", name, std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn wav_with_chunks(chunks: Vec<Chunk>) -> Vec<u8> {
+        let samples = (0..1000).map(|i| i as i16).collect();
+        let mut wav = Wav::from_data(Data::BitDepth16(samples), 48_000, 2);
+        wav.chunks = chunks;
+        wav.to_bytes()
+    }
+
+    fn junk(size: usize) -> Chunk {
+        Chunk {
+            id: ChunkTag::Junk,
+            bytes: vec![0; size],
+        }
+    }
+
+    fn ixml(text: &[u8]) -> Chunk {
+        Chunk {
+            id: ChunkTag::Unknown(*b"iXML"),
+            bytes: text.to_vec(),
+        }
+    }
+
+    #[test]
+    fn edit_within_junk() {
+        let bytes = wav_with_chunks(vec![junk(100)]);
+        let path = temp_file("edit_within_junk", &bytes);
+
+        let mut file = WavFile::open(&path).unwrap();
+        file.set_chunk(ixml(b"<BWFXML/>")).unwrap();
+        file.commit().unwrap();
+
+        let written = fs::read(&path).unwrap();
+        let wav = Wav::from_bytes(&written).unwrap();
+
+        // The data didn't move
+        assert_eq!(written.len(), bytes.len());
+        assert_eq!(written[written.len() - 2000..], bytes[bytes.len() - 2000..]);
+        assert_eq!(wav.chunks, [ixml(b"<BWFXML/>")]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn edit_moves_data_when_needed() {
+        let bytes = wav_with_chunks(vec![ixml(b"<a/>")]);
+        let path = temp_file("edit_moves_data", &bytes);
+        let text = vec![b'x'; 100_001];
+
+        let mut file = WavFile::open(&path).unwrap();
+        file.set_chunk(ixml(&text)).unwrap();
+        file.commit().unwrap();
+
+        let wav = Wav::from_bytes(&fs::read(&path).unwrap()).unwrap();
+
+        assert_eq!(wav.chunks, [ixml(&text)]);
+        assert_eq!(wav.data, Wav::from_bytes(&bytes).unwrap().data);
+
+        // Shrinking leaves the data in place and fills the gap
+        let mut file = WavFile::open(&path).unwrap();
+        file.remove_chunk(ChunkTag::Unknown(*b"iXML")).unwrap();
+        file.commit().unwrap();
+
+        let written = fs::read(&path).unwrap();
+        let wav = Wav::from_bytes(&written).unwrap();
+
+        assert_eq!(wav.chunks, []);
+        assert_eq!(&written[36..40], b"JUNK");
+        assert_eq!(wav.data, Wav::from_bytes(&bytes).unwrap().data);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn append_samples() {
+        let bytes = wav_with_chunks(vec![ixml(b"<a/>")]);
+        let path = temp_file("append_samples", &bytes);
+
+        let mut file = WavFile::open(&path).unwrap();
+        file.append(&Data::BitDepth16(vec![-1, -2, -3, -4]))
+            .unwrap();
+
+        assert_eq!(file.num_frames(), 502);
+        assert_eq!(
+            file.append(&Data::BitDepth16(vec![1])).unwrap_err(),
+            Error::IncompatibleData
+        );
+        assert_eq!(
+            file.append(&Data::BitDepth24(vec![1, 2])).unwrap_err(),
+            Error::IncompatibleData
+        );
+
+        let wav = Wav::from_bytes_with_options(&fs::read(&path).unwrap(), &ParseOptions::strict())
+            .unwrap()
+            .0;
+
+        let mut samples: Vec<i16> = (0..1000).map(|i| i as i16).collect();
+        samples.extend_from_slice(&[-1, -2, -3, -4]);
+
+        assert_eq!(wav.data, Data::BitDepth16(samples));
+        assert_eq!(wav.chunks, [ixml(b"<a/>")]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn overview_in_pieces() {
+        let samples: Vec<i16> = (0..80_000)
+            .map(|i| (i * 7 % 65_536 - 32_768) as i16)
+            .collect();
+        let wav = Wav::from_data(Data::BitDepth16(samples), 48_000, 2);
+        let path = temp_file("overview_in_pieces", &wav.to_bytes());
+
+        let mut file = WavFile::open(&path).unwrap();
+
+        assert_eq!(file.overview(300).unwrap(), wav.overview(300));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn checksum_in_pieces() {
+        let samples: Vec<i16> = (0..90_000)
+            .map(|i| (i * 13 % 65_536 - 32_768) as i16)
+            .collect();
+        let mut wav = Wav::from_data(Data::BitDepth16(samples), 44_100, 3);
+        wav.set_bext_md5();
+
+        let path = temp_file("checksum_in_pieces", &wav.to_bytes());
+        let mut file = WavFile::open(&path).unwrap();
+        let checksum = file.checksum(true).unwrap();
+
+        assert_eq!(checksum, wav.checksum(true));
+        assert_eq!(file.bext_md5(), Some(checksum.md5));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn checksum_of_ima_adpcm_in_pieces() {
+        let samples: Vec<i16> = (0..300_002)
+            .map(|i| (i * 17 % 8_000 - 4_000) as i16)
+            .collect();
+        let mut wav = Wav::from_data(Data::BitDepth16(samples), 48_000, 2);
+        wav.fmt.format = AudioFormat::ImaAdpcm {
+            samples_per_block: 505,
+        };
+
+        let bytes = wav.to_bytes();
+        let parsed = Wav::from_bytes(&bytes).unwrap();
+
+        let path = temp_file("checksum_of_ima_adpcm_in_pieces", &bytes);
+        let mut file = WavFile::open(&path).unwrap();
+
+        assert_eq!(file.checksum(true).unwrap(), parsed.checksum(true));
+        assert_eq!(file.overview(1000).unwrap(), parsed.overview(1000));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn append_requires_data_last() {
+        let mut bytes = wav_with_chunks(vec![]);
+        bytes.extend_from_slice(&ixml(b"<a/>").to_bytes());
+
+        let riff_size = (bytes.len() as u32 - 8).to_le_bytes();
+        bytes[4..8].copy_from_slice(&riff_size);
+
+        let path = temp_file("append_requires_data_last", &bytes);
+
+        let mut file = WavFile::open(&path).unwrap();
+        let data = Data::BitDepth16(vec![0, 0]);
+
+        assert_eq!(
+            file.append(&data).unwrap_err(),
+            Error::UnexpectedChunk(ChunkTag::Unknown(*b"iXML"))
+        );
+
+        file.commit().unwrap();
+        file.append(&data).unwrap();
+
+        let wav = Wav::from_bytes(&fs::read(&path).unwrap()).unwrap();
+
+        assert_eq!(wav.data.len(), 1002);
+        assert_eq!(wav.chunks, [ixml(b"<a/>")]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reject_audio_chunks() {
+        let path = temp_file("reject_audio_chunks", &wav_with_chunks(vec![]));
+        let mut file = WavFile::open(&path).unwrap();
+
+        assert_eq!(
+            file.remove_chunk(ChunkTag::Fmt).unwrap_err(),
+            Error::UnexpectedChunk(ChunkTag::Fmt)
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,71 +1,363 @@
 use crate::chunk::{Chunk, ChunkTag};
 use crate::error::Error;
-use alloc::vec;
-use core::convert::TryInto;
+use crate::ima_adpcm;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+/// Format tag of `WAVE_FORMAT_EXTENSIBLE`, the actual format is in the sub format GUID
+const EXTENSIBLE: u16 = 0xfffe;
+/// Bytes following the format code in the sub format GUID
+const SUB_FORMAT_GUID: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+/// Encoding of the sample data as declared by the `fmt_` chunk
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AudioFormat {
+    /// Uncompressed integer PCM samples
+    Pcm,
+    /// IMA/DVI ADPCM, 4 bits per sample packed in blocks of `samples_per_block` frames
+    ///
+    /// Files with an invalid number of samples per block are written as 16 bit PCM.
+    ImaAdpcm {
+        /// Number of frames encoded in every block, including the header sample, one more than
+        /// a multiple of 8
+        samples_per_block: u16,
+    },
+    /// Microsoft ADPCM, 4 bits per sample with the predictor coefficients stored in [`Fmt::extra`]
+    ///
+    /// Only decoding is supported, files in this format are written back as 16 bit PCM.
+    MsAdpcm {
+        /// Number of frames encoded in every block, including the two header samples
+        samples_per_block: u16,
+    },
+}
+
+impl AudioFormat {
+    /// Format tag as written in the `fmt_` chunk
+    pub fn tag(&self) -> u16 {
+        match self {
+            AudioFormat::Pcm => 0x0001,
+            AudioFormat::MsAdpcm { .. } => 0x0002,
+            AudioFormat::ImaAdpcm { .. } => 0x0011,
+        }
+    }
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioFormat::Pcm => write!(f, "PCM"),
+            AudioFormat::ImaAdpcm { samples_per_block } => {
+                write!(f, "IMA ADPCM ({} samples per block)", samples_per_block)
+            }
+            AudioFormat::MsAdpcm { samples_per_block } => {
+                write!(f, "MS ADPCM ({} samples per block)", samples_per_block)
+            }
+        }
+    }
+}
 
 /// Struct representing the `fmt_` section of a WAV file
 ///
 /// for more information see [`here`]
 ///
 /// [`here`]: http://soundfile.sapp.org/doc/WaveFormat/
+#[derive(Debug, PartialEq, Clone)]
 pub struct Fmt {
+    /// encoding of the sample data in the file, samples are always decoded to PCM when parsing
+    pub format: AudioFormat,
     /// sample rate, typical values are `44_100`, `48_000` or `96_000`
     pub sample_rate: u32,
     /// number of audio channels in the sample data, channels are interleaved
     pub num_channels: u16,
-    /// bit depth for each sample, typical values are `16` or `24`
+    /// bit depth for each decoded sample, typical values are `16` or `24`
+    ///
+    /// ADPCM samples are decoded to 16 bit, the 4 bit depth of the codec is implied by
+    /// `format`.
     pub bit_depth: u16,
+    /// codec specific bytes following the `cbSize` field of an extended fmt chunk
+    pub extra: Vec<u8>,
 }
 
 impl Fmt {
-    pub(crate) fn from_chunk(chunk: &Chunk) -> Result<Self, Error> {
-        let format = chunk.bytes[0..2]
-            .try_into()
-            .map_err(|_| Error::CantParseSliceInto)
-            .map(|b| u16::from_le_bytes(b))?;
+    /// Number of bytes in a single block of sample data.
+    ///
+    /// For PCM this is the size of a frame, for ADPCM the size of a compressed block. Sizes
+    /// beyond the range of the `fmt_` field saturate at `u16::MAX`.
+    pub fn block_align(&self) -> u16 {
+        self.block_size().min(u16::MAX as u64) as u16
+    }
+
+    /// Average number of bytes per second of sample data, saturating at `u32::MAX`.
+    pub fn byte_rate(&self) -> u32 {
+        self.bytes_per_second().min(u32::MAX as u64) as u32
+    }
+
+    /// Block size computed without overflowing for any field values.
+    fn block_size(&self) -> u64 {
+        let channels = self.num_channels as u64;
+
+        match self.format {
+            AudioFormat::Pcm => (channels * self.bit_depth as u64) / 8,
+            AudioFormat::ImaAdpcm { samples_per_block } => {
+                4 * channels + (samples_per_block.saturating_sub(1) as u64 / 2) * channels
+            }
+            AudioFormat::MsAdpcm { samples_per_block } => {
+                7 * channels + (samples_per_block.saturating_sub(2) as u64 * channels).div_ceil(2)
+            }
+        }
+    }
+
+    /// Byte rate computed without overflowing for any field values.
+    fn bytes_per_second(&self) -> u64 {
+        let sample_rate = self.sample_rate as u64;
+
+        match self.format {
+            AudioFormat::Pcm => {
+                (sample_rate * self.bit_depth as u64 * self.num_channels as u64) / 8
+            }
+            AudioFormat::ImaAdpcm { samples_per_block }
+            | AudioFormat::MsAdpcm { samples_per_block } => {
+                (sample_rate * self.block_size()) / (samples_per_block.max(1) as u64)
+            }
+        }
+    }
+
+    /// Speaker positions of the channels from an extensible fmt chunk, one bit per speaker.
+    ///
+    /// Bits are set in the order of the channels, starting with `0x1` for front left. A mask of
+    /// `0` means the channels aren't assigned to speakers.
+    pub fn channel_mask(&self) -> Option<u32> {
+        match (self.format, self.extra.get(0..22)) {
+            (AudioFormat::Pcm, Some(e)) if e[8..22] == SUB_FORMAT_GUID => {
+                Some(u32::from_le_bytes([e[2], e[3], e[4], e[5]]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Set the speaker positions, writing an extensible fmt chunk, see [`Fmt::channel_mask`].
+    ///
+    /// Only PCM can be written as extensible, `None` writes a plain fmt chunk.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let mut wav = Wav::from_data(Data::BitDepth16(vec![0; 6]), 48_000, 6);
+    /// wav.fmt.set_channel_mask(Some(0x3f));
+    ///
+    /// let wav = Wav::from_bytes(&wav.to_bytes()).unwrap();
+    ///
+    /// assert_eq!(wav.fmt.channel_mask(), Some(0x3f));
+    /// ```
+    pub fn set_channel_mask(&mut self, channel_mask: Option<u32>) {
+        if self.format != AudioFormat::Pcm {
+            return;
+        }
 
-        if format != 1 {
-            return Err(Error::UnsupportedFormat(format));
+        self.extra.clear();
+
+        if let Some(channel_mask) = channel_mask {
+            self.extra.extend_from_slice(&self.bit_depth.to_le_bytes());
+            self.extra.extend_from_slice(&channel_mask.to_le_bytes());
+            self.extra
+                .extend_from_slice(&self.format.tag().to_le_bytes());
+            self.extra.extend_from_slice(&SUB_FORMAT_GUID);
+        }
+    }
+
+    /// Index of the first sample of a frame in interleaved sample data.
+    pub fn frame_to_sample(&self, frame: usize) -> usize {
+        frame * self.num_channels as usize
+    }
+
+    /// Index of the frame holding a sample of interleaved sample data.
+    pub fn sample_to_frame(&self, sample: usize) -> usize {
+        sample / (self.num_channels as usize).max(1)
+    }
+
+    /// Time at which a frame starts.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![]), 48_000, 2);
+    ///
+    /// assert_eq!(wav.fmt.frame_to_time(24_000), Duration::from_millis(500));
+    /// assert_eq!(wav.fmt.time_to_frame(Duration::from_millis(500)), 24_000);
+    /// ```
+    pub fn frame_to_time(&self, frame: usize) -> Duration {
+        match self.sample_rate as u128 {
+            0 => Duration::from_secs(0),
+            rate => {
+                let nanos = frame as u128 * 1_000_000_000 / rate;
+                Duration::new(
+                    (nanos / 1_000_000_000) as u64,
+                    (nanos % 1_000_000_000) as u32,
+                )
+            }
+        }
+    }
+
+    /// Index of the frame playing at the given time, rounded down.
+    pub fn time_to_frame(&self, time: Duration) -> usize {
+        (time.as_nanos() * self.sample_rate as u128 / 1_000_000_000) as usize
+    }
+
+    /// Offset of a frame from the start of the `data` chunk body.
+    ///
+    /// For ADPCM this is the offset of the block holding the frame.
+    pub fn frame_to_byte_offset(&self, frame: usize) -> usize {
+        let block_align = self.block_align() as usize;
+
+        match self.format {
+            AudioFormat::Pcm => frame * block_align,
+            AudioFormat::ImaAdpcm { samples_per_block }
+            | AudioFormat::MsAdpcm { samples_per_block } => {
+                frame / (samples_per_block as usize).max(1) * block_align
+            }
         }
+    }
+
+    /// Index of the frame at an offset from the start of the `data` chunk body.
+    ///
+    /// For ADPCM this is the first frame of the block holding the offset.
+    pub fn byte_offset_to_frame(&self, offset: usize) -> usize {
+        let block_align = (self.block_align() as usize).max(1);
+
+        match self.format {
+            AudioFormat::Pcm => offset / block_align,
+            AudioFormat::ImaAdpcm { samples_per_block }
+            | AudioFormat::MsAdpcm { samples_per_block } => {
+                offset / block_align * samples_per_block as usize
+            }
+        }
+    }
+
+    /// 16 bit PCM representation of the same stream, used for formats that can't be encoded.
+    pub(crate) fn to_pcm(&self) -> Self {
+        Fmt {
+            format: AudioFormat::Pcm,
+            sample_rate: self.sample_rate,
+            num_channels: self.num_channels,
+            bit_depth: 16,
+            extra: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_chunk(chunk: &Chunk) -> Result<Self, Error> {
+        let format = chunk.read_u16(0)?;
+        let num_channels = chunk.read_u16(2)?;
+        let sample_rate = chunk.read_u32(4)?;
+        let bit_depth = chunk.read_u16(14)?;
+
+        // Extended fmt: cbSize followed by that many codec specific bytes
+        let extra = match chunk.bytes.get(16..18) {
+            Some(b) => {
+                let size = u16::from_le_bytes([b[0], b[1]]) as usize;
+                let end = (18 + size).min(chunk.bytes.len());
+                chunk.bytes[18..end].to_vec()
+            }
+            None => Vec::new(),
+        };
+
+        // Both ADPCM formats start their extension with the number of samples per block
+        let samples_per_block = chunk.read_u16(18);
+        let invalid_samples_per_block = Error::CantParseChunk {
+            tag: ChunkTag::Fmt,
+            offset: 18,
+        };
+
+        let format = match format {
+            0x0001 => AudioFormat::Pcm,
+            EXTENSIBLE => match chunk.read_u16(24)? {
+                0x0001 => AudioFormat::Pcm,
+                sub_format => return Err(Error::UnsupportedFormat(sub_format)),
+            },
+            0x0002 => {
+                let samples_per_block = samples_per_block?;
+
+                if samples_per_block < 2 {
+                    return Err(invalid_samples_per_block);
+                }
 
-        let num_channels = chunk.bytes[2..4]
-            .try_into()
-            .map_err(|_| Error::CantParseSliceInto)
-            .map(|b| u16::from_le_bytes(b))?;
+                AudioFormat::MsAdpcm { samples_per_block }
+            }
+            0x0011 => {
+                let samples_per_block = samples_per_block?;
 
-        let sample_rate = chunk.bytes[4..8]
-            .try_into()
-            .map_err(|_| Error::CantParseSliceInto)
-            .map(|b| u32::from_le_bytes(b))?;
+                if !ima_adpcm::is_valid_samples_per_block(samples_per_block as usize) {
+                    return Err(invalid_samples_per_block);
+                }
 
-        let bit_depth = chunk.bytes[14..16]
-            .try_into()
-            .map_err(|_| Error::CantParseSliceInto)
-            .map(|b| u16::from_le_bytes(b))?;
+                AudioFormat::ImaAdpcm { samples_per_block }
+            }
+            _ => return Err(Error::UnsupportedFormat(format)),
+        };
+
+        let bit_depth = match format {
+            AudioFormat::Pcm => bit_depth,
+            AudioFormat::ImaAdpcm { .. } | AudioFormat::MsAdpcm { .. } => 16,
+        };
 
         Ok(Fmt {
+            format,
             num_channels,
             sample_rate,
             bit_depth,
+            extra,
         })
     }
 
     pub(crate) fn to_chunk(&self) -> Chunk {
-        let br = ((self.sample_rate * (self.bit_depth as u32) * (self.num_channels as u32)) / 8)
-            .to_le_bytes();
-        let ba = ((self.num_channels * self.bit_depth) / 8).to_le_bytes();
-        let nc = self.num_channels.to_le_bytes();
-        let sr = self.sample_rate.to_le_bytes();
-        let bd = self.bit_depth.to_le_bytes();
+        let ima_samples_per_block = match self.format {
+            AudioFormat::Pcm => None,
+            AudioFormat::ImaAdpcm { samples_per_block }
+                if ima_adpcm::is_valid_samples_per_block(samples_per_block as usize) =>
+            {
+                Some(samples_per_block)
+            }
+            // MS ADPCM is only decoded, the samples are written as PCM like invalid IMA ADPCM
+            AudioFormat::ImaAdpcm { .. } | AudioFormat::MsAdpcm { .. } => {
+                return self.to_pcm().to_chunk()
+            }
+        };
 
-        let bytes = vec![
-            0x01, 0x00, // audio format
-            nc[0], nc[1], // num channels
-            sr[0], sr[1], sr[2], sr[3], // sample rate
-            br[0], br[1], br[2], br[3], // byte rate
-            ba[0], ba[1], // block align
-            bd[0], bd[1], // bits per sample
-        ];
+        let channel_mask = self.channel_mask();
+        let format = match channel_mask {
+            Some(_) => EXTENSIBLE,
+            None => self.format.tag(),
+        };
+
+        let mut bytes: Vec<u8> = Vec::with_capacity(40);
+
+        bytes.extend_from_slice(&format.to_le_bytes()); // audio format
+        bytes.extend_from_slice(&self.num_channels.to_le_bytes()); // num channels
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes()); // sample rate
+        bytes.extend_from_slice(&self.byte_rate().to_le_bytes()); // byte rate
+        bytes.extend_from_slice(&self.block_align().to_le_bytes()); // block align
+
+        match ima_samples_per_block {
+            None => {
+                bytes.extend_from_slice(&self.bit_depth.to_le_bytes()); // bits per sample
+
+                if let Some(channel_mask) = channel_mask {
+                    bytes.extend_from_slice(&22_u16.to_le_bytes()); // extension size
+                    bytes.extend_from_slice(&self.bit_depth.to_le_bytes()); // valid bits per sample
+                    bytes.extend_from_slice(&channel_mask.to_le_bytes()); // channel mask
+                    bytes.extend_from_slice(&self.format.tag().to_le_bytes()); // sub format
+                    bytes.extend_from_slice(&SUB_FORMAT_GUID);
+                }
+            }
+            Some(samples_per_block) => {
+                bytes.extend_from_slice(&4_u16.to_le_bytes()); // bits per sample
+                bytes.extend_from_slice(&2_u16.to_le_bytes()); // extension size
+                bytes.extend_from_slice(&samples_per_block.to_le_bytes()); // samples per block
+            }
+        }
 
         Chunk {
             id: ChunkTag::Fmt,
@@ -73,3 +365,211 @@ impl Fmt {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn parse_extensible_fmt() {
+        let bytes = vec![
+            0xfe, 0xff, // audio format
+            0x02, 0x00, // num channels
+            0x80, 0xbb, 0x00, 0x00, // sample rate
+            0x00, 0x65, 0x04, 0x00, // byte rate
+            0x06, 0x00, // block align
+            0x18, 0x00, // bits per sample
+            0x16, 0x00, // extension size
+            0x18, 0x00, // valid bits per sample
+            0x03, 0x00, 0x00, 0x00, // channel mask
+            0x01, 0x00, 0x00, 0x00, // sub format
+            0x00, 0x00, 0x10, 0x00, // ...
+            0x80, 0x00, 0x00, 0xaa, // ...
+            0x00, 0x38, 0x9b, 0x71, // ...
+        ];
+
+        let chunk = Chunk {
+            id: ChunkTag::Fmt,
+            bytes,
+        };
+
+        let fmt = Fmt::from_chunk(&chunk).unwrap();
+
+        assert_eq!(fmt.format, AudioFormat::Pcm);
+        assert_eq!(fmt.channel_mask(), Some(0x3));
+        assert_eq!(fmt.to_chunk(), chunk);
+
+        let mut float = chunk.clone();
+        float.bytes[24] = 0x03;
+
+        assert_eq!(
+            Fmt::from_chunk(&float).unwrap_err(),
+            Error::UnsupportedFormat(3)
+        );
+    }
+
+    #[test]
+    fn frame_positions() {
+        let fmt = Fmt {
+            format: AudioFormat::Pcm,
+            sample_rate: 44_100,
+            num_channels: 2,
+            bit_depth: 24,
+            extra: vec![],
+        };
+
+        assert_eq!(fmt.frame_to_sample(10), 20);
+        assert_eq!(fmt.sample_to_frame(21), 10);
+        assert_eq!(fmt.frame_to_byte_offset(10), 60);
+        assert_eq!(fmt.byte_offset_to_frame(65), 10);
+        assert_eq!(fmt.frame_to_time(66_150), Duration::from_millis(1500));
+        assert_eq!(fmt.frame_to_time(1), Duration::from_nanos(22_675));
+        assert_eq!(fmt.time_to_frame(Duration::from_nanos(22_675)), 0);
+        assert_eq!(fmt.time_to_frame(Duration::from_nanos(22_676)), 1);
+
+        let fmt = Fmt {
+            format: AudioFormat::ImaAdpcm {
+                samples_per_block: 505,
+            },
+            bit_depth: 16,
+            num_channels: 1,
+            ..fmt
+        };
+
+        assert_eq!(fmt.frame_to_byte_offset(1000), 256);
+        assert_eq!(fmt.byte_offset_to_frame(300), 505);
+    }
+
+    #[test]
+    fn parse_ima_adpcm_fmt() {
+        let bytes = vec![
+            0x11, 0x00, // audio format
+            0x01, 0x00, // num channels
+            0x40, 0x1f, 0x00, 0x00, // sample rate
+            0xd7, 0x0f, 0x00, 0x00, // byte rate
+            0x00, 0x01, // block align
+            0x04, 0x00, // bits per sample
+            0x02, 0x00, // extension size
+            0xf9, 0x01, // samples per block
+        ];
+
+        let chunk = Chunk {
+            id: ChunkTag::Fmt,
+            bytes: bytes.clone(),
+        };
+
+        let fmt = Fmt::from_chunk(&chunk).unwrap();
+
+        assert_eq!(
+            fmt.format,
+            AudioFormat::ImaAdpcm {
+                samples_per_block: 505
+            }
+        );
+        assert_eq!(fmt.bit_depth, 16);
+        assert_eq!(fmt.extra, [0xf9, 0x01]);
+        assert_eq!(fmt.block_align(), 256);
+        assert_eq!(fmt.to_chunk().bytes, bytes);
+    }
+
+    #[test]
+    fn parse_ms_adpcm_fmt() {
+        let chunk = Chunk {
+            id: ChunkTag::Fmt,
+            bytes: vec![
+                0x02, 0x00, // audio format
+                0x01, 0x00, // num channels
+                0x22, 0x56, 0x00, 0x00, // sample rate
+                0x93, 0x2b, 0x00, 0x00, // byte rate
+                0x00, 0x02, // block align
+                0x04, 0x00, // bits per sample
+                0x08, 0x00, // extension size
+                0xf4, 0x03, // samples per block
+                0x01, 0x00, // num coefficients
+                0x00, 0x01, 0x00, 0x00, // coefficient pair
+                0xaa, 0xaa, // trailing bytes outside of the extension
+            ],
+        };
+
+        let fmt = Fmt::from_chunk(&chunk).unwrap();
+
+        assert_eq!(
+            fmt.format,
+            AudioFormat::MsAdpcm {
+                samples_per_block: 1012
+            }
+        );
+        assert_eq!(fmt.block_align(), 512);
+        assert_eq!(fmt.extra, [0xf4, 0x03, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00]);
+
+        // Written as 16 bit PCM
+        let written = Fmt::from_chunk(&fmt.to_chunk()).unwrap();
+
+        assert_eq!(written, fmt.to_pcm());
+        assert_eq!(written.block_align(), 2);
+    }
+
+    #[test]
+    fn sizes_of_many_channels() {
+        let mut fmt = Fmt {
+            format: AudioFormat::Pcm,
+            sample_rate: 48_000,
+            num_channels: 0x2000,
+            bit_depth: 16,
+            extra: vec![],
+        };
+
+        assert_eq!(fmt.block_align(), 16_384);
+        assert_eq!(fmt.byte_rate(), 786_432_000);
+
+        fmt.num_channels = u16::MAX;
+        fmt.bit_depth = 24;
+        fmt.sample_rate = u32::MAX;
+
+        assert_eq!(fmt.block_align(), u16::MAX);
+        assert_eq!(fmt.byte_rate(), u32::MAX);
+
+        fmt.format = AudioFormat::ImaAdpcm {
+            samples_per_block: u16::MAX,
+        };
+
+        assert_eq!(fmt.block_align(), u16::MAX);
+        assert_eq!(fmt.byte_rate(), u32::MAX);
+    }
+
+    #[test]
+    fn reject_truncated_fmt() {
+        let chunk = Chunk {
+            id: ChunkTag::Fmt,
+            bytes: vec![0x01, 0x00, 0x01, 0x00, 0x80, 0xbb, 0x00, 0x00],
+        };
+
+        assert_eq!(
+            Fmt::from_chunk(&chunk).unwrap_err(),
+            Error::CantParseSliceInto {
+                tag: ChunkTag::Fmt,
+                offset: 14
+            }
+        );
+    }
+
+    #[test]
+    fn reject_invalid_samples_per_block() {
+        let chunk = Chunk {
+            id: ChunkTag::Fmt,
+            bytes: vec![
+                0x11, 0x00, 0x01, 0x00, 0x40, 0x1f, 0x00, 0x00, 0xd7, 0x0f, 0x00, 0x00, 0x00, 0x01,
+                0x04, 0x00, 0x02, 0x00, 0xf8, 0x01,
+            ],
+        };
+
+        assert_eq!(
+            Fmt::from_chunk(&chunk).unwrap_err(),
+            Error::CantParseChunk {
+                tag: ChunkTag::Fmt,
+                offset: 18
+            }
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! IMA/DVI ADPCM (format `0x0011`) block codec.
+//!
+//! Every block starts with a 4 byte header per channel (initial predictor as `i16`, step
+//! index as `u8` and a reserved byte), followed by groups of 4 bytes per channel holding 8
+//! nibbles each, lowest nibble first.
+
+use crate::chunk::ChunkTag;
+use crate::error::Error;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const INDEX_TABLE: [i8; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+#[derive(Clone, Copy, Default)]
+struct State {
+    predictor: i32,
+    index: i32,
+}
+
+impl State {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[self.index as usize];
+        let mut diff = step >> 3;
+
+        if nibble & 4 != 0 {
+            diff += step;
+        }
+        if nibble & 2 != 0 {
+            diff += step >> 1;
+        }
+        if nibble & 1 != 0 {
+            diff += step >> 2;
+        }
+
+        if nibble & 8 != 0 {
+            self.predictor -= diff;
+        } else {
+            self.predictor += diff;
+        }
+
+        self.predictor = self.predictor.clamp(i16::MIN as i32, i16::MAX as i32);
+        self.index = (self.index + INDEX_TABLE[nibble as usize] as i32).clamp(0, 88);
+
+        self.predictor as i16
+    }
+
+    fn encode(&mut self, sample: i16) -> u8 {
+        let step = STEP_TABLE[self.index as usize];
+        let mut diff = sample as i32 - self.predictor;
+        let mut nibble = 0;
+
+        if diff < 0 {
+            nibble = 8;
+            diff = -diff;
+        }
+
+        let mut threshold = step;
+
+        for bit in [4, 2, 1] {
+            if diff >= threshold {
+                nibble |= bit;
+                diff -= threshold;
+            }
+            threshold >>= 1;
+        }
+
+        // Run the decoder so the encoder tracks exactly what will be reconstructed
+        self.decode(nibble);
+
+        nibble
+    }
+}
+
+/// Blocks hold a header sample followed by groups of 8 frames.
+pub(crate) fn is_valid_samples_per_block(samples_per_block: usize) -> bool {
+    samples_per_block != 0 && (samples_per_block - 1).is_multiple_of(8)
+}
+
+/// Number of bytes in a block holding `samples_per_block` frames of `num_channels` channels.
+pub(crate) fn block_align(samples_per_block: usize, num_channels: usize) -> usize {
+    4 * num_channels + ((samples_per_block - 1) / 2) * num_channels
+}
+
+/// Decode IMA ADPCM blocks into interleaved 16 bit samples.
+///
+/// `num_frames` is taken from the `fact` chunk and used to discard the padding in the last
+/// block, when it is absent every complete or partial block is decoded in full.
+pub(crate) fn decode(
+    bytes: &[u8],
+    num_channels: usize,
+    samples_per_block: usize,
+    num_frames: Option<usize>,
+) -> Result<Vec<i16>, Error> {
+    if num_channels == 0 || !is_valid_samples_per_block(samples_per_block) {
+        return Err(Error::CantParseChunk {
+            tag: ChunkTag::Data,
+            offset: 0,
+        });
+    }
+
+    let block_size = block_align(samples_per_block, num_channels);
+    let mut samples = vec![];
+
+    for block in bytes.chunks(block_size) {
+        if block.len() < 4 * num_channels {
+            break;
+        }
+
+        let mut states: Vec<State> = block
+            .chunks_exact(4)
+            .take(num_channels)
+            .map(|header| State {
+                predictor: i16::from_le_bytes([header[0], header[1]]) as i32,
+                index: (header[2] as i32).min(88),
+            })
+            .collect();
+
+        let start = samples.len();
+        let body = &block[4 * num_channels..];
+        let frames = 1 + (body.len() / (4 * num_channels)) * 8;
+
+        samples.resize(start + frames * num_channels, 0);
+
+        for (channel, state) in states.iter().enumerate() {
+            samples[start + channel] = state.predictor as i16;
+        }
+
+        // Each group of `4 * num_channels` bytes holds 8 frames
+        for (group, bytes) in body.chunks_exact(4 * num_channels).enumerate() {
+            for (channel, state) in states.iter_mut().enumerate() {
+                let nibbles = &bytes[channel * 4..channel * 4 + 4];
+
+                for (i, byte) in nibbles.iter().enumerate() {
+                    let frame = 1 + group * 8 + i * 2;
+
+                    samples[start + frame * num_channels + channel] = state.decode(byte & 0x0f);
+                    samples[start + (frame + 1) * num_channels + channel] = state.decode(byte >> 4);
+                }
+            }
+        }
+    }
+
+    if let Some(num_frames) = num_frames {
+        samples.truncate(num_frames * num_channels);
+    }
+
+    Ok(samples)
+}
+
+/// Encode interleaved 16 bit samples into IMA ADPCM blocks.
+///
+/// The last block is padded with silence, the real number of frames has to be stored in a
+/// `fact` chunk alongside the data.
+pub(crate) fn encode(samples: &[i16], num_channels: usize, samples_per_block: usize) -> Vec<u8> {
+    let frame_count = samples.len() / num_channels.max(1);
+    let mut bytes = vec![];
+
+    if num_channels == 0 || frame_count == 0 {
+        return bytes;
+    }
+
+    let mut states = vec![State::default(); num_channels];
+    let frame = |index: usize, channel: usize| -> i16 {
+        samples
+            .get(index * num_channels + channel)
+            .copied()
+            .unwrap_or(0)
+    };
+
+    for block_start in (0..frame_count).step_by(samples_per_block.max(1)) {
+        for (channel, state) in states.iter_mut().enumerate() {
+            state.predictor = frame(block_start, channel) as i32;
+
+            bytes.extend_from_slice(&(state.predictor as i16).to_le_bytes());
+            bytes.push(state.index as u8);
+            bytes.push(0);
+        }
+
+        for group in 0..samples_per_block.saturating_sub(1) / 8 {
+            for (channel, state) in states.iter_mut().enumerate() {
+                for i in 0..4 {
+                    let index = block_start + 1 + group * 8 + i * 2;
+                    let low = state.encode(frame(index, channel));
+                    let high = state.encode(frame(index + 1, channel));
+
+                    bytes.push(low | (high << 4));
+                }
+            }
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_block() {
+        let bytes = [
+            0x10, 0x00, 0x00, 0x00, // header: predictor 16, index 0
+            0x77, 0x77, 0x77, 0x77, // 8 nibbles of +7
+        ];
+
+        let samples = decode(&bytes, 1, 9, None).unwrap();
+
+        assert_eq!(samples, [16, 27, 57, 120, 256, 549, 1180, 2537, 5447]);
+    }
+
+    #[test]
+    fn decode_respects_frame_count() {
+        let bytes = [
+            0x10, 0x00, 0x00, 0x00, // header: predictor 16, index 0
+            0x77, 0x77, 0x77, 0x77, // 8 nibbles of +7
+        ];
+
+        let samples = decode(&bytes, 1, 9, Some(3)).unwrap();
+
+        assert_eq!(samples, [16, 27, 57]);
+    }
+
+    #[test]
+    fn encode_and_decode_stereo() {
+        let samples: Vec<i16> = (0..200)
+            .flat_map(|i| {
+                // Triangle wave with a slope the step table can follow
+                let t = i % 100;
+                let s = if t < 50 { t * 40 } else { (100 - t) * 40 } as i16 - 1000;
+                [s, -s]
+            })
+            .collect();
+
+        let bytes = encode(&samples, 2, 17);
+
+        assert_eq!(bytes.len() % block_align(17, 2), 0);
+
+        let decoded = decode(&bytes, 2, 17, Some(200)).unwrap();
+
+        assert_eq!(decoded.len(), samples.len());
+
+        for (a, b) in samples.iter().zip(decoded.iter()) {
+            assert!((*a as i32 - *b as i32).abs() < 64);
+        }
+    }
+}
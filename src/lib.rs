@@ -24,9 +24,9 @@
 //!
 //! Writing a WAV file:
 //! ```
+//! use std::env;
 //! use std::fs::File;
 //! use std::io::Write;
-//! use std::path::Path;
 //! use wavv::{Wav, Data};
 //!
 //! fn main() {
@@ -34,25 +34,63 @@
 //!     let data = Data::BitDepth16(vec![0; 480_000]);
 //! 	let wav = Wav::from_data(data, 48_000, 2);
 //!
-//!     let path = Path::new("output.wav");
+//!     let path = env::temp_dir().join("output.wav");
 //!     let mut file = File::create(&path).unwrap();
 //!     file.write_all(&wav.to_bytes()).unwrap();
 //! }
 //! ```
 
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![warn(missing_docs)]
+#![allow(clippy::tabs_in_doc_comments)]
 
 extern crate alloc;
 
+mod analysis;
+mod channels;
+mod checksum;
 mod chunk;
+mod compare;
 mod data;
+mod edit;
 mod error;
+mod fade;
+#[cfg(feature = "std")]
+mod file;
 mod fmt;
+mod generate;
+mod ima_adpcm;
+mod loudness;
+mod matrix;
+mod ms_adpcm;
+mod overview;
+mod parse;
+mod process;
+mod riff;
+mod sf2;
+mod silence;
+mod spectrum;
 mod wav;
+mod write;
 
+pub use analysis::ChannelStats;
+pub use checksum::Checksum;
 pub use chunk::{Chunk, ChunkTag};
+pub use compare::Comparison;
 pub use data::Data;
 pub use error::Error;
-pub use fmt::Fmt;
+pub use fade::FadeCurve;
+#[cfg(feature = "std")]
+pub use file::WavFile;
+pub use fmt::{AudioFormat, Fmt};
+pub use generate::Signal;
+pub use loudness::Loudness;
+pub use matrix::ChannelMatrix;
+pub use overview::{Overview, OverviewBlock, OverviewBuilder};
+pub use parse::{ParseMode, ParseOptions, ParseWarning, ParseWarningKind};
+pub use process::Overflow;
+pub use riff::{List, Node, Riff};
+pub use sf2::{SampleHeader, SoundFont};
+pub use spectrum::{Spectrogram, Stft, Window};
 pub use wav::Wav;
+pub use write::WriteOptions;
@@ -0,0 +1,552 @@
+//! Loudness measurement following ITU-R BS.1770-4 and EBU R128 / Tech 3342.
+//!
+//! Samples are K-weighted per channel and summed by channel weight, loudness is measured over
+//! 400 ms (momentary) and 3 s (short-term) windows moving in steps of 100 ms.
+
+use crate::chunk::{Chunk, ChunkTag};
+use crate::error::Error;
+use crate::process::{gain_to_db, Overflow};
+use crate::wav::Wav;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+/// Number of steps of 100 ms in a momentary window
+const MOMENTARY_STEPS: usize = 4;
+/// Number of steps of 100 ms in a short-term window
+const SHORT_TERM_STEPS: usize = 30;
+/// Blocks below this loudness are ignored
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks this far below the ungated loudness are ignored for the integrated loudness
+const RELATIVE_GATE: f64 = -10.0;
+/// Windows this far below the ungated loudness are ignored for the loudness range
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+/// Size of a `bext` chunk without coding history
+pub(crate) const BEXT_SIZE: usize = 602;
+/// Offset of the version in the `bext` chunk
+const BEXT_VERSION: usize = 346;
+/// Offset of the loudness fields added in version 2 of the `bext` chunk
+const BEXT_LOUDNESS: usize = 412;
+/// Loudness field value for levels that aren't known
+const BEXT_UNKNOWN: i16 = 0x7fff;
+/// Oversampling factor of the true peak measurement
+const OVERSAMPLING: usize = 4;
+/// Number of input samples on either side of an interpolated sample
+const INTERPOLATION_TAPS: i32 = 6;
+
+/// Loudness of a [`Wav`], see [`Wav::loudness`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Loudness {
+    /// Gated loudness of the whole file in LUFS
+    pub integrated: f64,
+    /// Loudness range in LU, the spread of the short-term loudness
+    pub range: f64,
+    /// Highest level of the 4 times oversampled signal of any channel in dBTP
+    pub true_peak: f64,
+    /// Highest momentary loudness in LUFS
+    pub max_momentary: f64,
+    /// Highest short-term loudness in LUFS
+    pub max_short_term: f64,
+    /// Loudness of every 400 ms window in LUFS, in steps of 100 ms
+    pub momentary: Vec<f64>,
+    /// Loudness of every 3 s window in LUFS, in steps of 100 ms
+    pub short_term: Vec<f64>,
+}
+
+/// Second order IIR filter in direct form I
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    /// First stage of the K-weighting, a high shelf modelling the acoustic effect of the head.
+    fn high_shelf(sample_rate: f64) -> Self {
+        let gain = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = libm::tan(PI * 1_681.974_450_955_533 / sample_rate);
+
+        let vh = libm::pow(10.0, gain / 20.0);
+        let vb = libm::pow(vh, 0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    /// Second stage of the K-weighting, a high pass filter.
+    fn high_pass(sample_rate: f64) -> Self {
+        let q = 0.500_327_037_323_877_3;
+        let k = libm::tan(PI * 38.135_470_876_024_44 / sample_rate);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+
+        y
+    }
+}
+
+impl Wav {
+    /// Measure the loudness according to ITU-R BS.1770-4 and EBU R128.
+    ///
+    /// Channels are weighted by their speaker position from the channel mask, or the default
+    /// layout for the number of channels: surround channels count 1.5 dB more, the LFE channel
+    /// is left out. Files shorter than a window have a loudness of [`f64::NEG_INFINITY`].
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// // 1 kHz sine at -20 dBFS on both channels
+    /// let samples = (0..96_000)
+    ///     .map(|i| {
+    ///         let phase = (i / 2) as f64 / 48.0 * 2.0 * std::f64::consts::PI;
+    ///         (phase.sin() * 0.1 * 32768.0) as i16
+    ///     })
+    ///     .collect();
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(samples), 48_000, 2);
+    /// let loudness = wav.loudness().unwrap();
+    ///
+    /// assert!((loudness.integrated + 20.0).abs() < 0.1);
+    /// assert!((loudness.true_peak + 20.0).abs() < 0.1);
+    /// ```
+    pub fn loudness(&self) -> Result<Loudness, Error> {
+        let num_channels = self.fmt.num_channels as usize;
+        let sample_rate = self.fmt.sample_rate as usize;
+
+        if num_channels == 0 || sample_rate < 10 {
+            return Err(Error::IncompatibleData);
+        }
+
+        let weights = channel_weights(self.fmt.channel_mask(), num_channels);
+        let samples = self.data.to_f64_samples();
+
+        // Mean square of every 100 ms step, summed over the weighted channels
+        let step_size = sample_rate / 10;
+        let mut filters = vec![
+            (
+                Biquad::high_shelf(sample_rate as f64),
+                Biquad::high_pass(sample_rate as f64)
+            );
+            num_channels
+        ];
+        let mut steps = Vec::with_capacity(samples.len() / (step_size * num_channels) + 1);
+        let mut energy = 0.0;
+
+        for (i, frame) in samples.chunks_exact(num_channels).enumerate() {
+            for ((sample, (shelf, pass)), weight) in frame.iter().zip(&mut filters).zip(&weights) {
+                let y = pass.process(shelf.process(*sample));
+                energy += weight * y * y;
+            }
+
+            if (i + 1) % step_size == 0 {
+                steps.push(energy / step_size as f64);
+                energy = 0.0;
+            }
+        }
+
+        let momentary = windows(&steps, MOMENTARY_STEPS);
+        let short_term = windows(&steps, SHORT_TERM_STEPS);
+
+        Ok(Loudness {
+            integrated: integrated(&momentary),
+            range: range(&short_term),
+            true_peak: true_peak(&samples, num_channels),
+            max_momentary: momentary.iter().fold(f64::NEG_INFINITY, |a, b| a.max(*b)),
+            max_short_term: short_term.iter().fold(f64::NEG_INFINITY, |a, b| a.max(*b)),
+            momentary,
+            short_term,
+        })
+    }
+
+    /// Change the level to reach `target` LUFS without true peaks above `ceiling` dBTP.
+    ///
+    /// When the ceiling is hit the level is raised less, no limiting is applied. Silence is
+    /// left untouched. The loudness after normalising is returned and written to the `bext`
+    /// chunk, see [`Wav::set_bext_loudness`].
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let samples = (0..96_000)
+    ///     .map(|i| {
+    ///         let phase = (i / 2) as f64 / 48.0 * 2.0 * std::f64::consts::PI;
+    ///         (phase.sin() * 0.1 * 32768.0) as i16
+    ///     })
+    ///     .collect();
+    ///
+    /// let mut wav = Wav::from_data(Data::BitDepth16(samples), 48_000, 2);
+    /// let loudness = wav.normalize_loudness(-23.0, -1.0).unwrap();
+    ///
+    /// assert!((loudness.integrated + 23.0).abs() < 0.1);
+    /// ```
+    pub fn normalize_loudness(&mut self, target: f64, ceiling: f64) -> Result<Loudness, Error> {
+        let loudness = self.loudness()?;
+
+        let loudness = if loudness.integrated.is_finite() {
+            let gain = (target - loudness.integrated).min(ceiling - loudness.true_peak);
+
+            self.data.gain(gain, Overflow::Clip)?;
+            self.loudness()?
+        } else {
+            loudness
+        };
+
+        self.set_bext_loudness(&loudness);
+
+        Ok(loudness)
+    }
+
+    /// Write the loudness to the version 2 fields of the `bext` chunk, adding the chunk when
+    /// missing.
+    ///
+    /// Levels are stored in hundredths, unknown levels as `0x7fff`.
+    pub fn set_bext_loudness(&mut self, loudness: &Loudness) {
+        let tag = ChunkTag::from_bytes(b"bext");
+
+        let index = match self.chunks.iter().position(|c| c.id == tag) {
+            Some(index) => index,
+            None => {
+                self.chunks.push(Chunk {
+                    id: tag,
+                    bytes: vec![0; BEXT_SIZE],
+                });
+
+                self.chunks.len() - 1
+            }
+        };
+
+        let bytes = &mut self.chunks[index].bytes;
+
+        if bytes.len() < BEXT_SIZE {
+            bytes.resize(BEXT_SIZE, 0);
+        }
+
+        let version = u16::from_le_bytes([bytes[BEXT_VERSION], bytes[BEXT_VERSION + 1]]);
+        bytes[BEXT_VERSION..BEXT_VERSION + 2].copy_from_slice(&version.max(2).to_le_bytes());
+
+        let fields = [
+            loudness.integrated,
+            loudness.range,
+            loudness.true_peak,
+            loudness.max_momentary,
+            loudness.max_short_term,
+        ];
+
+        for (i, value) in fields.iter().enumerate() {
+            let value = if value.is_finite() {
+                libm::round(value * 100.0).clamp(i16::MIN as f64, (BEXT_UNKNOWN - 1) as f64) as i16
+            } else {
+                BEXT_UNKNOWN
+            };
+
+            let offset = BEXT_LOUDNESS + i * 2;
+            bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Weight of every channel, from the speaker positions or the default layout.
+fn channel_weights(channel_mask: Option<u32>, num_channels: usize) -> Vec<f64> {
+    let channel_mask = match (channel_mask, num_channels) {
+        (Some(mask), _) if mask != 0 => mask,
+        (_, 5) => 0x37,  // L R C Ls Rs
+        (_, 6) => 0x3f,  // L R C LFE Ls Rs
+        (_, 7) => 0x13f, // L R C LFE Ls Rs Cs
+        (_, 8) => 0x63f, // L R C LFE Ls Rs Lss Rss
+        _ => return vec![1.0; num_channels],
+    };
+
+    let mut weights: Vec<f64> = (0..32)
+        .map(|bit| 1_u32 << bit)
+        .filter(|speaker| channel_mask & speaker != 0)
+        .map(|speaker| match speaker {
+            // Low frequency effects
+            0x8 => 0.0,
+            // Back left, back right, side left and side right
+            0x10 | 0x20 | 0x200 | 0x400 => 1.41,
+            _ => 1.0,
+        })
+        .collect();
+
+    weights.resize(num_channels, 1.0);
+    weights
+}
+
+/// Loudness of every window of `size` steps.
+fn windows(steps: &[f64], size: usize) -> Vec<f64> {
+    steps
+        .windows(size)
+        .map(|w| energy_to_loudness(w.iter().sum::<f64>() / size as f64))
+        .collect()
+}
+
+fn energy_to_loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * libm::log10(energy)
+}
+
+fn loudness_to_energy(loudness: f64) -> f64 {
+    libm::pow(10.0, (loudness + 0.691) / 10.0)
+}
+
+/// Loudness of the mean energy of the blocks above `gate`.
+fn gated_mean(blocks: &[f64], gate: f64) -> Option<f64> {
+    let gated: Vec<f64> = blocks.iter().copied().filter(|b| *b > gate).collect();
+
+    match gated.len() {
+        0 => None,
+        n => Some(energy_to_loudness(
+            gated.iter().map(|b| loudness_to_energy(*b)).sum::<f64>() / n as f64,
+        )),
+    }
+}
+
+/// Integrated loudness of the momentary blocks with absolute and relative gating.
+fn integrated(blocks: &[f64]) -> f64 {
+    gated_mean(blocks, ABSOLUTE_GATE)
+        .and_then(|ungated| gated_mean(blocks, ungated + RELATIVE_GATE))
+        .unwrap_or(f64::NEG_INFINITY)
+}
+
+/// Loudness range, from the 10th to the 95th percentile of the gated short-term windows.
+fn range(windows: &[f64]) -> f64 {
+    let gate = match gated_mean(windows, ABSOLUTE_GATE) {
+        Some(ungated) => ungated + RANGE_RELATIVE_GATE,
+        None => return 0.0,
+    };
+
+    let mut gated: Vec<f64> = windows
+        .iter()
+        .copied()
+        .filter(|w| *w > gate && *w > ABSOLUTE_GATE)
+        .collect();
+
+    gated.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+
+    let percentile = |p: f64| gated[libm::round((gated.len() - 1) as f64 * p) as usize];
+
+    percentile(0.95) - percentile(0.10)
+}
+
+/// Highest absolute level of any channel oversampled by windowed sinc interpolation.
+fn true_peak(samples: &[f64], num_channels: usize) -> f64 {
+    // Interpolation kernel of every phase between two input samples
+    let kernels: Vec<Vec<f64>> = (1..OVERSAMPLING)
+        .map(|phase| {
+            let offset = phase as f64 / OVERSAMPLING as f64;
+
+            (1 - INTERPOLATION_TAPS..=INTERPOLATION_TAPS)
+                .map(|tap| {
+                    let t = tap as f64 - offset;
+                    let sinc = libm::sin(PI * t) / (PI * t);
+                    let window = 0.5 * (1.0 + libm::cos(PI * t / INTERPOLATION_TAPS as f64));
+
+                    sinc * window
+                })
+                .collect()
+        })
+        .collect();
+
+    let num_frames = samples.len() / num_channels;
+    let mut peak = 0.0_f64;
+
+    for channel in 0..num_channels {
+        let sample = |i: isize| {
+            if i < 0 || i as usize >= num_frames {
+                0.0
+            } else {
+                samples[i as usize * num_channels + channel]
+            }
+        };
+
+        for frame in 0..num_frames as isize {
+            peak = peak.max(sample(frame).abs());
+
+            for kernel in &kernels {
+                let interpolated: f64 = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, k)| k * sample(frame + i as isize + 1 - INTERPOLATION_TAPS as isize))
+                    .sum();
+
+                peak = peak.max(interpolated.abs());
+            }
+        }
+    }
+
+    gain_to_db(peak)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Data;
+
+    fn sine(frequency: f64, level: f64, sample_rate: usize, seconds: usize) -> Vec<f64> {
+        (0..sample_rate * seconds)
+            .map(|i| {
+                libm::sin(2.0 * PI * frequency * i as f64 / sample_rate as f64)
+                    * libm::pow(10.0, level / 20.0)
+            })
+            .collect()
+    }
+
+    fn wav(samples: &[f64], sample_rate: usize, num_channels: usize) -> Wav {
+        let interleaved: Vec<f64> = samples
+            .iter()
+            .flat_map(|s| core::iter::repeat_n(*s, num_channels))
+            .collect();
+        let (data, _) = Data::from_f64_samples(&interleaved, 24).unwrap();
+
+        Wav::from_data(data, sample_rate, num_channels)
+    }
+
+    #[test]
+    fn k_weighting_48_khz() {
+        // Coefficients from ITU-R BS.1770-4
+        let shelf = Biquad::high_shelf(48_000.0);
+        let pass = Biquad::high_pass(48_000.0);
+
+        let expected = [
+            1.535_124_859_586_97,
+            -2.691_696_189_406_38,
+            1.198_392_810_852_85,
+        ];
+
+        for (b, e) in shelf.b.iter().zip(&expected) {
+            assert!((b - e).abs() < 1e-9);
+        }
+
+        assert!((shelf.a[0] + 1.690_659_293_182_41).abs() < 1e-9);
+        assert!((shelf.a[1] - 0.732_480_774_215_85).abs() < 1e-9);
+        assert!((pass.a[0] + 1.990_047_454_833_98).abs() < 1e-9);
+        assert!((pass.a[1] - 0.990_072_250_366_21).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sine_at_every_sample_rate() {
+        // A 1 kHz sine at -23 dBFS on both stereo channels reads -23 LUFS (EBU Tech 3341)
+        for sample_rate in &[44_100, 48_000, 96_000] {
+            let loudness = wav(&sine(1000.0, -23.0, *sample_rate, 5), *sample_rate, 2)
+                .loudness()
+                .unwrap();
+
+            assert!((loudness.integrated + 23.0).abs() < 0.1);
+            assert!((loudness.max_momentary + 23.0).abs() < 0.1);
+            assert!((loudness.max_short_term + 23.0).abs() < 0.1);
+            assert!(loudness.range < 0.1);
+            assert_eq!(loudness.momentary.len(), 47);
+            assert_eq!(loudness.short_term.len(), 21);
+        }
+    }
+
+    #[test]
+    fn loudness_range() {
+        // Like EBU Tech 3342 case 1, a step from -20 LUFS to -30 LUFS
+        let mut samples = sine(1000.0, -20.0, 48_000, 10);
+        samples.extend(sine(1000.0, -30.0, 48_000, 10));
+
+        let loudness = wav(&samples, 48_000, 2).loudness().unwrap();
+
+        assert!((loudness.range - 10.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn surround_weights() {
+        assert_eq!(channel_weights(None, 6), [1.0, 1.0, 1.0, 0.0, 1.41, 1.41]);
+        assert_eq!(channel_weights(Some(0x3), 2), [1.0, 1.0]);
+        assert_eq!(channel_weights(None, 3), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn inter_sample_peaks() {
+        // A sine at a quarter of the sample rate sampled 45 degrees off its peaks
+        let samples: Vec<f64> = (0..4800)
+            .map(|i| libm::sin(PI / 2.0 * i as f64 + PI / 4.0) * 0.5)
+            .collect();
+
+        let sample_peak = gain_to_db(0.5 * core::f64::consts::FRAC_1_SQRT_2);
+
+        assert!((true_peak(&samples, 1) - gain_to_db(0.5)).abs() < 0.2);
+        assert!(true_peak(&samples, 1) > sample_peak + 2.5);
+    }
+
+    #[test]
+    fn normalize_to_ceiling() {
+        let mut wav = wav(&sine(1000.0, -30.0, 48_000, 3), 48_000, 2);
+        let loudness = wav.normalize_loudness(-3.0, -6.0).unwrap();
+
+        assert!((loudness.true_peak + 6.0).abs() < 0.1);
+        assert!((loudness.integrated + 6.0).abs() < 0.1);
+
+        let bext = &wav.chunks[0];
+
+        assert_eq!(bext.id, ChunkTag::from_bytes(b"bext"));
+        assert_eq!(bext.bytes.len(), BEXT_SIZE);
+        assert_eq!(bext.read_u16(BEXT_VERSION).unwrap(), 2);
+        assert_eq!(
+            bext.read_u16(BEXT_LOUDNESS).unwrap() as i16,
+            libm::round(loudness.integrated * 100.0) as i16
+        );
+        assert_eq!(
+            bext.read_u16(BEXT_LOUDNESS + 4).unwrap() as i16,
+            libm::round(loudness.true_peak * 100.0) as i16
+        );
+    }
+
+    #[test]
+    fn bext_loudness_of_silence() {
+        let mut wav = wav(&[0.0; 100], 48_000, 1);
+        wav.chunks.push(Chunk {
+            id: ChunkTag::from_bytes(b"bext"),
+            bytes: b"Description".to_vec(),
+        });
+
+        wav.normalize_loudness(-23.0, -1.0).unwrap();
+
+        let bext = &wav.chunks[0];
+
+        assert!(bext.bytes.starts_with(b"Description\0"));
+        assert_eq!(bext.read_u16(BEXT_LOUDNESS).unwrap(), 0x7fff);
+        assert_eq!(bext.read_u16(BEXT_LOUDNESS + 2).unwrap(), 0);
+    }
+
+    #[test]
+    fn silence_and_short_files() {
+        let loudness = wav(&[0.0; 100], 48_000, 1).loudness().unwrap();
+
+        assert_eq!(loudness.integrated, f64::NEG_INFINITY);
+        assert_eq!(loudness.range, 0.0);
+        assert!(loudness.momentary.is_empty());
+    }
+}
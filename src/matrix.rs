@@ -0,0 +1,266 @@
+use crate::data::Data;
+use crate::error::Error;
+use crate::process::Overflow;
+use crate::wav::Wav;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// -3 dB, the level of centre and surround channels in a downmix
+const MINUS_3_DB: f64 = core::f64::consts::FRAC_1_SQRT_2;
+
+/// Gains from every input channel to every output channel, see [`Wav::remap`].
+///
+/// Channels are numbered in the order they are interleaved, for 5.1 WAV files this is
+/// `L R C LFE Ls Rs` (SMPTE order).
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChannelMatrix {
+    /// One row per output channel, holding the gain of every input channel
+    pub gains: Vec<Vec<f64>>,
+    /// Channel mask of the output, `None` for channels not assigned to speakers
+    pub channel_mask: Option<u32>,
+}
+
+impl ChannelMatrix {
+    /// Matrix with one row of input gains per output channel.
+    ///
+    /// ```
+    /// use wavv::ChannelMatrix;
+    ///
+    /// // Stereo to mono
+    /// let matrix = ChannelMatrix::new(vec![vec![0.5, 0.5]]);
+    /// ```
+    pub fn new(gains: Vec<Vec<f64>>) -> Self {
+        ChannelMatrix {
+            gains,
+            channel_mask: None,
+        }
+    }
+
+    /// Reorder channels, output channel `i` is input channel `order[i]`.
+    pub fn reorder(num_inputs: usize, order: &[usize]) -> Self {
+        let gains = order
+            .iter()
+            .map(|input| {
+                let mut row = vec![0.0; num_inputs];
+
+                if let Some(gain) = row.get_mut(*input) {
+                    *gain = 1.0;
+                }
+
+                row
+            })
+            .collect();
+
+        ChannelMatrix::new(gains)
+    }
+
+    /// 5.1 from SMPTE order (`L R C LFE Ls Rs`) to film order (`L C R Ls Rs LFE`).
+    pub fn smpte_to_film() -> Self {
+        ChannelMatrix::reorder(6, &[0, 2, 1, 4, 5, 3])
+    }
+
+    /// 5.1 from film order (`L C R Ls Rs LFE`) to SMPTE order (`L R C LFE Ls Rs`).
+    pub fn film_to_smpte() -> Self {
+        ChannelMatrix {
+            channel_mask: Some(0x3f),
+            ..ChannelMatrix::reorder(6, &[0, 2, 1, 5, 3, 4])
+        }
+    }
+
+    /// ITU-R BS.775 downmix of 5.1 in SMPTE order to stereo, the LFE channel is dropped.
+    pub fn downmix_5_1_to_stereo() -> Self {
+        ChannelMatrix {
+            gains: vec![
+                vec![1.0, 0.0, MINUS_3_DB, 0.0, MINUS_3_DB, 0.0],
+                vec![0.0, 1.0, MINUS_3_DB, 0.0, 0.0, MINUS_3_DB],
+            ],
+            channel_mask: Some(0x3),
+        }
+    }
+
+    /// ITU-R BS.775 downmix of stereo to mono.
+    pub fn downmix_stereo_to_mono() -> Self {
+        ChannelMatrix {
+            gains: vec![vec![0.5, 0.5]],
+            channel_mask: Some(0x4),
+        }
+    }
+
+    /// Mono to dual mono, the same signal on both channels.
+    pub fn mono_to_stereo() -> Self {
+        ChannelMatrix {
+            gains: vec![vec![1.0], vec![1.0]],
+            channel_mask: Some(0x3),
+        }
+    }
+
+    /// Swap the left and right channels of stereo.
+    pub fn swap_left_right() -> Self {
+        ChannelMatrix {
+            gains: vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+            channel_mask: Some(0x3),
+        }
+    }
+
+    /// Stereo to mid/side, `M = (L + R) / 2` and `S = (L - R) / 2`.
+    pub fn mid_side_encode() -> Self {
+        ChannelMatrix::new(vec![vec![0.5, 0.5], vec![0.5, -0.5]])
+    }
+
+    /// Mid/side to stereo, `L = M + S` and `R = M - S`.
+    pub fn mid_side_decode() -> Self {
+        ChannelMatrix {
+            gains: vec![vec![1.0, 1.0], vec![1.0, -1.0]],
+            channel_mask: Some(0x3),
+        }
+    }
+}
+
+impl Wav {
+    /// New [`Wav`] with channels computed from the input channels by a [`ChannelMatrix`].
+    ///
+    /// Every row of the matrix needs a gain for every channel, files without channels can't be
+    /// remapped. The other chunks are copied as they are.
+    ///
+    /// ```
+    /// use wavv::{ChannelMatrix, Data, Overflow, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![100, 200, 300, 400]), 48_000, 2);
+    /// let swapped = wav
+    ///     .remap(&ChannelMatrix::swap_left_right(), Overflow::Clip)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(swapped.data, Data::BitDepth16(vec![200, 100, 400, 300]));
+    ///
+    /// let mono = wav
+    ///     .remap(&ChannelMatrix::downmix_stereo_to_mono(), Overflow::Clip)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(mono.fmt.num_channels, 1);
+    /// assert_eq!(mono.data, Data::BitDepth16(vec![150, 350]));
+    /// ```
+    pub fn remap(&self, matrix: &ChannelMatrix, overflow: Overflow) -> Result<Wav, Error> {
+        let num_inputs = self.fmt.num_channels as usize;
+        let num_outputs = matrix.gains.len();
+
+        if num_inputs == 0
+            || num_outputs == 0
+            || num_outputs > u16::MAX as usize
+            || matrix.gains.iter().any(|row| row.len() != num_inputs)
+        {
+            return Err(Error::IncompatibleData);
+        }
+
+        let input = self.data.to_f64_samples();
+        let mut output = Vec::with_capacity(self.num_frames() * num_outputs);
+
+        for frame in input.chunks_exact(num_inputs) {
+            for row in &matrix.gains {
+                output.push(row.iter().zip(frame).map(|(gain, s)| gain * s).sum());
+            }
+        }
+
+        let (data, clipped) = Data::from_f64_samples(&output, self.data.bit_depth())?;
+
+        if overflow == Overflow::Report && clipped > 0 {
+            return Err(Error::Clipped(clipped));
+        }
+
+        let mut fmt = self.fmt.clone();
+        fmt.num_channels = num_outputs as u16;
+        fmt.set_channel_mask(matrix.channel_mask);
+
+        Ok(Wav {
+            fmt,
+            data,
+            chunks: self.chunks.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_5_1() {
+        let wav = Wav::from_data(
+            Data::BitDepth16(vec![1000, 2000, 10_000, 30_000, 100, 200]),
+            48_000,
+            6,
+        );
+
+        let stereo = wav
+            .remap(&ChannelMatrix::downmix_5_1_to_stereo(), Overflow::Report)
+            .unwrap();
+
+        assert_eq!(stereo.fmt.num_channels, 2);
+        assert_eq!(stereo.fmt.block_align(), 4);
+        assert_eq!(stereo.fmt.channel_mask(), Some(0x3));
+        assert_eq!(stereo.data, Data::BitDepth16(vec![8142, 9212]));
+    }
+
+    #[test]
+    fn reorder_film_and_smpte() {
+        let smpte = Wav::from_data(Data::BitDepth8(vec![0, 1, 2, 3, 4, 5]), 48_000, 6);
+
+        let film = smpte
+            .remap(&ChannelMatrix::smpte_to_film(), Overflow::Report)
+            .unwrap();
+
+        assert_eq!(film.data, Data::BitDepth8(vec![0, 2, 1, 4, 5, 3]));
+
+        let back = film
+            .remap(&ChannelMatrix::film_to_smpte(), Overflow::Report)
+            .unwrap();
+
+        assert_eq!(back.data, smpte.data);
+        assert_eq!(back.fmt.channel_mask(), Some(0x3f));
+    }
+
+    #[test]
+    fn mid_side_round_trip() {
+        let wav = Wav::from_data(Data::BitDepth24(vec![4000, -2000, 32, 64]), 48_000, 2);
+
+        let mid_side = wav
+            .remap(&ChannelMatrix::mid_side_encode(), Overflow::Report)
+            .unwrap();
+
+        assert_eq!(mid_side.data, Data::BitDepth24(vec![1000, 3000, 48, -16]));
+        assert_eq!(mid_side.fmt.channel_mask(), None);
+
+        let stereo = mid_side
+            .remap(&ChannelMatrix::mid_side_decode(), Overflow::Report)
+            .unwrap();
+
+        assert_eq!(stereo.data, wav.data);
+    }
+
+    #[test]
+    fn reject_mismatched_matrix() {
+        let wav = Wav::from_data(Data::BitDepth16(vec![0]), 48_000, 1);
+
+        assert_eq!(
+            wav.remap(&ChannelMatrix::swap_left_right(), Overflow::Clip)
+                .unwrap_err(),
+            Error::IncompatibleData
+        );
+
+        let dual = wav
+            .remap(&ChannelMatrix::mono_to_stereo(), Overflow::Clip)
+            .unwrap();
+
+        assert_eq!(dual.data, Data::BitDepth16(vec![0, 0]));
+
+        let empty = Wav::from_data(Data::BitDepth16(vec![0]), 48_000, 0);
+        let matrix = ChannelMatrix {
+            gains: vec![vec![]],
+            channel_mask: None,
+        };
+
+        assert_eq!(
+            empty.remap(&matrix, Overflow::Clip).unwrap_err(),
+            Error::IncompatibleData
+        );
+    }
+}
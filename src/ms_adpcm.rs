@@ -0,0 +1,241 @@
+//! Microsoft ADPCM (format `0x0002`) block decoder.
+//!
+//! Every block starts with a header of a predictor index (`u8`), an initial delta (`i16`) and
+//! two initial samples (`i16`) for each channel, followed by nibbles interleaved per channel,
+//! highest nibble first.
+
+use crate::chunk::ChunkTag;
+use crate::error::Error;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+/// Range of the delta, the upper bound keeps the adaptation from overflowing
+const MIN_DELTA: i32 = 16;
+const MAX_DELTA: i32 = i32::MAX / 768;
+
+/// Coefficient pairs every MS ADPCM encoder is required to write to the fmt extension.
+const DEFAULT_COEFFICIENTS: [[i32; 2]; 7] = [
+    [256, 0],
+    [512, -256],
+    [0, 0],
+    [192, 64],
+    [240, 0],
+    [460, -208],
+    [392, -232],
+];
+
+struct State {
+    coefficients: [i32; 2],
+    delta: i32,
+    sample1: i32,
+    sample2: i32,
+}
+
+impl State {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        // Sign extend the 4 bit value
+        let signed = ((nibble << 4) as i8 >> 4) as i32;
+        // Custom coefficients can exceed the range of an i32 when multiplied and summed
+        let predictor = (self.sample1 as i64 * self.coefficients[0] as i64
+            + self.sample2 as i64 * self.coefficients[1] as i64)
+            >> 8;
+        let sample = (predictor + (signed * self.delta) as i64)
+            .clamp(i16::MIN as i64, i16::MAX as i64) as i32;
+
+        self.sample2 = self.sample1;
+        self.sample1 = sample;
+        self.delta =
+            ((ADAPTATION_TABLE[nibble as usize] * self.delta) >> 8).clamp(MIN_DELTA, MAX_DELTA);
+
+        sample as i16
+    }
+}
+
+/// Parse the predictor coefficients from the fmt extension.
+///
+/// The extension holds the number of samples per block, the number of coefficient pairs and the
+/// pairs themselves, the standard table is used when it is missing.
+fn parse_coefficients(extra: &[u8]) -> Vec<[i32; 2]> {
+    let count = extra
+        .get(2..4)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+        .unwrap_or(0);
+
+    let coefficients: Vec<[i32; 2]> = extra
+        .get(4..)
+        .unwrap_or(&[])
+        .chunks_exact(4)
+        .take(count)
+        .map(|b| {
+            [
+                i16::from_le_bytes([b[0], b[1]]) as i32,
+                i16::from_le_bytes([b[2], b[3]]) as i32,
+            ]
+        })
+        .collect();
+
+    if coefficients.is_empty() {
+        DEFAULT_COEFFICIENTS.to_vec()
+    } else {
+        coefficients
+    }
+}
+
+/// Decode MS ADPCM blocks into interleaved 16 bit samples.
+///
+/// `num_frames` is taken from the `fact` chunk and used to discard the padding in the last
+/// block, when it is absent every complete or partial block is decoded in full.
+pub(crate) fn decode(
+    bytes: &[u8],
+    num_channels: usize,
+    block_align: usize,
+    extra: &[u8],
+    num_frames: Option<usize>,
+) -> Result<Vec<i16>, Error> {
+    let header_size = 7 * num_channels;
+
+    if num_channels == 0 || block_align < header_size {
+        return Err(Error::CantParseChunk {
+            tag: ChunkTag::Data,
+            offset: 0,
+        });
+    }
+
+    let coefficients = parse_coefficients(extra);
+    let mut samples = vec![];
+
+    for (index, block) in bytes.chunks(block_align).enumerate() {
+        if block.len() < header_size {
+            break;
+        }
+
+        let word = |index: usize| {
+            let pos = num_channels + index * 2;
+            i16::from_le_bytes([block[pos], block[pos + 1]]) as i32
+        };
+
+        let mut states = vec![];
+
+        for (channel, predictor) in block[..num_channels].iter().enumerate() {
+            let coefficients =
+                *coefficients
+                    .get(*predictor as usize)
+                    .ok_or(Error::CantParseChunk {
+                        tag: ChunkTag::Data,
+                        offset: index * block_align + channel,
+                    })?;
+
+            states.push(State {
+                coefficients,
+                delta: word(channel).clamp(MIN_DELTA, MAX_DELTA),
+                sample1: word(num_channels + channel),
+                sample2: word(2 * num_channels + channel),
+            });
+        }
+
+        // The oldest header sample comes first
+        samples.extend(states.iter().map(|s| s.sample2 as i16));
+        samples.extend(states.iter().map(|s| s.sample1 as i16));
+
+        let mut channel = 0;
+
+        for byte in &block[header_size..] {
+            for nibble in [byte >> 4, byte & 0x0f] {
+                samples.push(states[channel].decode(nibble));
+                channel = (channel + 1) % num_channels;
+            }
+        }
+
+        // An odd number of nibbles for multiple channels leaves an incomplete frame
+        samples.truncate(samples.len() - samples.len() % num_channels);
+    }
+
+    if let Some(num_frames) = num_frames {
+        samples.truncate(num_frames * num_channels);
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_block() {
+        let bytes = [
+            0x00, // predictor index
+            0x10, 0x00, // delta
+            0x64, 0x00, // sample 1
+            0x32, 0x00, // sample 2
+            0x12, 0xf0, // nibbles
+        ];
+
+        let samples = decode(&bytes, 1, 9, &[], None).unwrap();
+
+        assert_eq!(samples, [50, 100, 116, 148, 132, 132]);
+    }
+
+    #[test]
+    fn decode_stereo_with_custom_coefficients() {
+        let extra = [
+            0x04, 0x00, // samples per block
+            0x01, 0x00, // num coefficients
+            0x00, 0x00, 0x00, 0x00, // coefficient pair (0, 0)
+        ];
+
+        let bytes = [
+            0x00, 0x00, // predictor indices
+            0x10, 0x00, 0x10, 0x00, // deltas
+            0x01, 0x00, 0x02, 0x00, // sample 1 L+R
+            0x03, 0x00, 0x04, 0x00, // sample 2 L+R
+            0x11, 0x22, // nibbles L, R, L, R
+        ];
+
+        let samples = decode(&bytes, 2, 16, &extra, Some(4)).unwrap();
+
+        assert_eq!(samples, [3, 4, 1, 2, 16, 16, 32, 32]);
+    }
+
+    #[test]
+    fn reject_unknown_predictor() {
+        let bytes = [0x07, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        assert_eq!(
+            decode(&bytes, 1, 8, &[], None).unwrap_err(),
+            Error::CantParseChunk {
+                tag: ChunkTag::Data,
+                offset: 0
+            }
+        );
+    }
+
+    #[test]
+    fn adversarial_block() {
+        let mut bytes = vec![
+            0x00, // predictor index
+            0xff, 0x7f, // delta
+            0x00, 0x80, // sample 1
+            0x00, 0x80, // sample 2
+        ];
+        bytes.extend_from_slice(&[0x88; 64]);
+
+        let samples = decode(&bytes, 1, bytes.len(), &[], None).unwrap();
+        assert_eq!(samples.len(), 130);
+
+        // Negative delta and extreme custom coefficients
+        let extra = [
+            0x04, 0x00, // samples per block
+            0x01, 0x00, // num coefficients
+            0x00, 0x80, 0x00, 0x80, // coefficient pair (-32768, -32768)
+        ];
+        bytes[1..3].copy_from_slice(&[0x00, 0x80]);
+
+        let samples = decode(&bytes, 1, bytes.len(), &extra, None).unwrap();
+        assert_eq!(samples[2], i16::MAX);
+    }
+}
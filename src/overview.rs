@@ -0,0 +1,424 @@
+//! Waveform overviews for drawing zoomable waveforms.
+//!
+//! An [`Overview`] holds the minimum, maximum and RMS level of every block of frames per
+//! channel. It can be computed from a [`Wav`] in memory, or from samples arriving in pieces
+//! with an [`OverviewBuilder`]. Overviews are written as BBC audiowaveform `.dat` files, to
+//! be stored alongside the audio, or as an EBU Tech 3285 `levl` chunk to embed in the WAV.
+
+use crate::chunk::{Chunk, ChunkTag};
+use crate::data::Data;
+use crate::error::Error;
+use crate::fmt::Fmt;
+use crate::wav::Wav;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Version of the audiowaveform format supporting multiple channels
+const DAT_VERSION: i32 = 2;
+/// audiowaveform flag for 8 bit values
+const DAT_8_BIT: u32 = 1;
+/// Size of the `levl` chunk header, the peak values follow
+const LEVL_HEADER_SIZE: usize = 120;
+/// `levl` format of unsigned 8 bit peak values
+const LEVL_FORMAT_8_BIT: u32 = 1;
+/// `levl` format of unsigned 16 bit peak values
+const LEVL_FORMAT_16_BIT: u32 = 2;
+/// `levl` position of the peak of peaks when it isn't known
+const LEVL_UNKNOWN_POSITION: u32 = 0xffff_ffff;
+
+/// Levels of a block of frames of a single channel, in the range `-1.0..1.0`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct OverviewBlock {
+    /// Lowest sample value
+    pub min: f64,
+    /// Highest sample value
+    pub max: f64,
+    /// Root mean square of the samples
+    pub rms: f64,
+}
+
+/// Levels per block of frames for every channel
+///
+/// Written as a BBC audiowaveform `.dat` file with [`Overview::to_audiowaveform`], to be
+/// stored alongside the audio, or as an EBU Tech 3285 `levl` chunk with
+/// [`Overview::to_levl_chunk`] to embed in the WAV.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Overview {
+    /// Sample rate of the audio
+    pub sample_rate: u32,
+    /// Number of frames per block, only the last block can be shorter
+    pub frames_per_block: usize,
+    /// Blocks of every channel
+    pub channels: Vec<Vec<OverviewBlock>>,
+    /// Frame of the highest absolute sample value, `None` for silence
+    pub peak_frame: Option<usize>,
+}
+
+/// Running sums of the current block of a single channel
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    min: f64,
+    max: f64,
+    sum_of_squares: f64,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator {
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum_of_squares: 0.0,
+        }
+    }
+}
+
+/// Computes an [`Overview`] from interleaved samples pushed in pieces of any size.
+///
+/// ```
+/// use wavv::{Data, OverviewBuilder, Wav};
+///
+/// let wav = Wav::from_data(Data::BitDepth16(vec![]), 48_000, 2);
+/// let mut builder = OverviewBuilder::new(&wav.fmt, 2);
+///
+/// builder.push(&Data::BitDepth16(vec![16384, 0, -16384]));
+/// builder.push(&Data::BitDepth16(vec![0, 8192, 0]));
+///
+/// let overview = builder.finish();
+///
+/// assert_eq!(overview.channels[0].len(), 2);
+/// assert_eq!(overview.channels[0][0].min, -0.5);
+/// assert_eq!(overview.channels[0][0].max, 0.5);
+/// assert_eq!(overview.channels[1][0].max, 0.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OverviewBuilder {
+    overview: Overview,
+    blocks: Vec<Accumulator>,
+    channel: usize,
+    frame: usize,
+    frames_in_block: usize,
+    peak: f64,
+}
+
+impl OverviewBuilder {
+    /// Builder for audio in the given format, with blocks of `frames_per_block` frames.
+    pub fn new(fmt: &Fmt, frames_per_block: usize) -> Self {
+        let num_channels = fmt.num_channels as usize;
+
+        OverviewBuilder {
+            overview: Overview {
+                sample_rate: fmt.sample_rate,
+                frames_per_block: frames_per_block.max(1),
+                channels: vec![vec![]; num_channels],
+                peak_frame: None,
+            },
+            blocks: vec![Accumulator::default(); num_channels],
+            channel: 0,
+            frame: 0,
+            frames_in_block: 0,
+            peak: 0.0,
+        }
+    }
+
+    /// Add interleaved samples, continuing where the previous samples left off.
+    pub fn push(&mut self, data: &Data) {
+        let num_channels = self.blocks.len();
+
+        if num_channels == 0 {
+            return;
+        }
+
+        for sample in data.to_f64_samples() {
+            let block = &mut self.blocks[self.channel];
+
+            block.min = block.min.min(sample);
+            block.max = block.max.max(sample);
+            block.sum_of_squares += sample * sample;
+
+            if sample.abs() > self.peak {
+                self.peak = sample.abs();
+                self.overview.peak_frame = Some(self.frame);
+            }
+
+            self.channel += 1;
+
+            if self.channel == num_channels {
+                self.channel = 0;
+                self.frame += 1;
+                self.frames_in_block += 1;
+
+                if self.frames_in_block == self.overview.frames_per_block {
+                    self.flush();
+                }
+            }
+        }
+    }
+
+    /// Overview of every sample pushed, a partial block at the end is kept.
+    pub fn finish(mut self) -> Overview {
+        if self.frames_in_block > 0 || self.channel > 0 {
+            self.flush();
+        }
+
+        self.overview
+    }
+
+    fn flush(&mut self) {
+        let frames_in_block = self.frames_in_block;
+
+        for (channel, block) in self.blocks.iter_mut().enumerate() {
+            // A partial frame leaves the last channels without samples
+            let num_samples = frames_in_block + usize::from(channel < self.channel);
+
+            let levels = match num_samples {
+                0 => OverviewBlock {
+                    min: 0.0,
+                    max: 0.0,
+                    rms: 0.0,
+                },
+                n => OverviewBlock {
+                    min: block.min,
+                    max: block.max,
+                    rms: libm::sqrt(block.sum_of_squares / n as f64),
+                },
+            };
+
+            self.overview.channels[channel].push(levels);
+            *block = Accumulator::default();
+        }
+
+        self.frames_in_block = 0;
+    }
+}
+
+impl Overview {
+    /// Number of blocks per channel.
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, |c| c.len())
+    }
+
+    /// Returns true if the overview holds no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// BBC audiowaveform `.dat` file (version 2) with 8 or 16 bit min/max values.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![-16384, 16384]), 48_000, 1);
+    /// let dat = wav.overview(256).to_audiowaveform(8).unwrap();
+    ///
+    /// assert_eq!(dat.len(), 24 + 2);
+    /// assert_eq!(dat[24..], [(-64_i8) as u8, 64]);
+    /// ```
+    pub fn to_audiowaveform(&self, bit_depth: u16) -> Result<Vec<u8>, Error> {
+        let flags = match bit_depth {
+            8 => DAT_8_BIT,
+            16 => 0,
+            _ => return Err(Error::UnsupportedBitDepth(bit_depth)),
+        };
+
+        let scale = (1_u32 << (bit_depth - 1)) as f64;
+        let quantize = |value: f64| libm::round(value * scale).clamp(-scale, scale - 1.0) as i16;
+
+        let mut bytes = vec![];
+
+        bytes.extend_from_slice(&DAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate as i32).to_le_bytes());
+        bytes.extend_from_slice(&(self.frames_per_block as i32).to_le_bytes());
+        bytes.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.channels.len() as i32).to_le_bytes());
+
+        for index in 0..self.len() {
+            for channel in &self.channels {
+                for value in [channel[index].min, channel[index].max] {
+                    match bit_depth {
+                        8 => bytes.push(quantize(value) as i8 as u8),
+                        _ => bytes.extend_from_slice(&quantize(value).to_le_bytes()),
+                    }
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// EBU Tech 3285 peak envelope (`levl`) chunk with 8 or 16 bit positive and negative
+    /// peak values.
+    ///
+    /// The timestamp of the chunk is left empty.
+    pub fn to_levl_chunk(&self, bit_depth: u16) -> Result<Chunk, Error> {
+        match bit_depth {
+            8 => Ok(self.levl_chunk(LEVL_FORMAT_8_BIT)),
+            16 => Ok(self.levl_chunk(LEVL_FORMAT_16_BIT)),
+            _ => Err(Error::UnsupportedBitDepth(bit_depth)),
+        }
+    }
+
+    /// `levl` chunk in one of the `LEVL_FORMAT_*` formats.
+    fn levl_chunk(&self, format: u32) -> Chunk {
+        let bit_depth = match format {
+            LEVL_FORMAT_8_BIT => 8,
+            _ => 16,
+        };
+
+        let scale = ((1_u32 << (bit_depth - 1)) - 1) as f64;
+        let quantize = |value: f64| libm::round(value.abs().min(1.0) * scale) as u16;
+
+        let peak_frame = self
+            .peak_frame
+            .map_or(LEVL_UNKNOWN_POSITION, |frame| frame as u32);
+
+        let mut bytes = vec![];
+
+        // Version
+        bytes.extend_from_slice(&0_u32.to_le_bytes());
+        bytes.extend_from_slice(&format.to_le_bytes());
+        // Points per value, positive and negative peaks
+        bytes.extend_from_slice(&2_u32.to_le_bytes());
+        bytes.extend_from_slice(&(self.frames_per_block as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.channels.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&peak_frame.to_le_bytes());
+        // Offset to the peak values, from the start of the chunk header
+        bytes.extend_from_slice(&(8 + LEVL_HEADER_SIZE as u32).to_le_bytes());
+        bytes.resize(LEVL_HEADER_SIZE, 0);
+
+        for index in 0..self.len() {
+            for channel in &self.channels {
+                for value in [channel[index].max.max(0.0), channel[index].min.min(0.0)] {
+                    match bit_depth {
+                        8 => bytes.push(quantize(value) as u8),
+                        _ => bytes.extend_from_slice(&quantize(value).to_le_bytes()),
+                    }
+                }
+            }
+        }
+
+        Chunk {
+            id: ChunkTag::from_bytes(b"levl"),
+            bytes,
+        }
+    }
+}
+
+impl Wav {
+    /// Levels of every block of `frames_per_block` frames, see [`OverviewBuilder`].
+    pub fn overview(&self, frames_per_block: usize) -> Overview {
+        let mut builder = OverviewBuilder::new(&self.fmt, frames_per_block);
+        builder.push(&self.data);
+        builder.finish()
+    }
+
+    /// Embed the overview as a 16 bit `levl` chunk, replacing an existing one.
+    pub fn set_levl(&mut self, overview: &Overview) {
+        let chunk = overview.levl_chunk(LEVL_FORMAT_16_BIT);
+
+        match self.chunks.iter_mut().find(|c| c.id == chunk.id) {
+            Some(existing) => *existing = chunk,
+            None => self.chunks.push(chunk),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_per_channel() {
+        let wav = Wav::from_data(
+            Data::BitDepth8(vec![192, 128, 64, 128, 128, 160, 128, 96, 255]),
+            8000,
+            2,
+        );
+
+        let overview = wav.overview(2);
+
+        assert_eq!(overview.len(), 3);
+        assert_eq!(overview.peak_frame, Some(4));
+
+        let left = &overview.channels[0];
+        let right = &overview.channels[1];
+
+        assert_eq!((left[0].min, left[0].max, left[0].rms), (-0.5, 0.5, 0.5));
+        assert_eq!(
+            (right[1].min, right[1].max, right[1].rms),
+            (-0.25, 0.25, 0.25)
+        );
+        assert_eq!(left[2].max, 127.0 / 128.0);
+
+        // The partial frame at the end has no right channel sample
+        assert_eq!(right[2].max, 0.0);
+    }
+
+    #[test]
+    fn pushed_in_pieces() {
+        let samples: Vec<i16> = (0..1000).map(|i| ((i * 37) % 2001 - 1000) as i16).collect();
+        let wav = Wav::from_data(Data::BitDepth16(samples.clone()), 48_000, 2);
+
+        let mut builder = OverviewBuilder::new(&wav.fmt, 64);
+
+        for piece in samples.chunks(77) {
+            builder.push(&Data::BitDepth16(piece.to_vec()));
+        }
+
+        assert_eq!(builder.finish(), wav.overview(64));
+    }
+
+    #[test]
+    fn audiowaveform_header() {
+        let wav = Wav::from_data(Data::BitDepth16(vec![-32768, 32767, 0, 100]), 44_100, 2);
+        let dat = wav.overview(512).to_audiowaveform(16).unwrap();
+
+        assert_eq!(
+            dat,
+            [
+                2, 0, 0, 0, // version
+                0, 0, 0, 0, // flags
+                0x44, 0xac, 0, 0, // sample rate
+                0, 2, 0, 0, // samples per pixel
+                1, 0, 0, 0, // length
+                2, 0, 0, 0, // channels
+                0x00, 0x80, 0x00, 0x00, // left min and max
+                0x64, 0x00, 0xff, 0x7f, // right min and max
+            ]
+        );
+
+        assert_eq!(
+            wav.overview(512).to_audiowaveform(24).unwrap_err(),
+            Error::UnsupportedBitDepth(24)
+        );
+    }
+
+    #[test]
+    fn levl_chunk() {
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![16384, -32768, 0, 0]), 48_000, 1);
+        let overview = wav.overview(2);
+
+        let chunk = overview.to_levl_chunk(8).unwrap();
+
+        assert_eq!(chunk.id, ChunkTag::from_bytes(b"levl"));
+        assert_eq!(chunk.bytes.len(), LEVL_HEADER_SIZE + 4);
+        assert_eq!(chunk.read_u32(4).unwrap(), LEVL_FORMAT_8_BIT);
+        assert_eq!(chunk.read_u32(12).unwrap(), 2);
+        assert_eq!(chunk.read_u32(16).unwrap(), 1);
+        assert_eq!(chunk.read_u32(20).unwrap(), 2);
+        assert_eq!(chunk.read_u32(24).unwrap(), 1);
+        assert_eq!(chunk.read_u32(28).unwrap(), 128);
+        assert_eq!(chunk.bytes[LEVL_HEADER_SIZE..], [64, 127, 0, 0]);
+
+        wav.set_levl(&overview);
+        wav.set_levl(&overview);
+
+        let parsed = Wav::from_bytes(&wav.to_bytes()).unwrap();
+        let levl: Vec<_> = parsed.chunks.iter().filter(|c| c.id == chunk.id).collect();
+
+        assert_eq!(levl.len(), 1);
+        assert_eq!(levl[0].read_u32(4).unwrap(), LEVL_FORMAT_16_BIT);
+        assert_eq!(levl[0].read_u16(LEVL_HEADER_SIZE + 2).unwrap(), 32767);
+    }
+}
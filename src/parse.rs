@@ -0,0 +1,166 @@
+use crate::error::Error;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// How to treat inconsistencies between the headers and the actual contents of a file
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ParseMode {
+    /// Accept the file, reporting every inconsistency as a [`ParseWarning`]
+    #[default]
+    Lenient,
+    /// Fail on the first inconsistency with [`Error::Inconsistent`]
+    Strict,
+    /// Like [`ParseMode::Lenient`], additionally repairing files from crashed recorders or
+    /// streaming writers
+    ///
+    /// A RIFF size of `0` or `0xffffffff` is taken to span the whole file, a `data` chunk size
+    /// of `0`, `0xffffffff` or beyond the end of the file is clamped to the available bytes.
+    /// Every repair is reported as a [`ParseWarning`].
+    Recover,
+}
+
+/// Options for [`Wav::from_bytes_with_options`](crate::Wav::from_bytes_with_options)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Strict or lenient handling of inconsistencies
+    pub mode: ParseMode,
+}
+
+impl ParseOptions {
+    /// Options that fail on any inconsistency
+    pub fn strict() -> Self {
+        ParseOptions {
+            mode: ParseMode::Strict,
+        }
+    }
+
+    /// Options that accept inconsistent files and report warnings
+    pub fn lenient() -> Self {
+        ParseOptions {
+            mode: ParseMode::Lenient,
+        }
+    }
+
+    /// Options that repair truncated files and files with streaming headers
+    pub fn recover() -> Self {
+        ParseOptions {
+            mode: ParseMode::Recover,
+        }
+    }
+}
+
+/// Kind of inconsistency found while parsing
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ParseWarningKind {
+    /// The RIFF chunk size does not match the number of bytes following it
+    RiffSizeMismatch {
+        /// Size written in the RIFF header
+        declared: u32,
+        /// Number of bytes actually following the RIFF header
+        actual: u32,
+    },
+    /// The byte rate in the `fmt_` chunk does not match the sample rate and block align
+    ByteRateMismatch {
+        /// Byte rate written in the `fmt_` chunk
+        declared: u32,
+        /// Byte rate derived from the other `fmt_` fields
+        expected: u32,
+    },
+    /// The block align in the `fmt_` chunk does not match the channels and bit depth
+    BlockAlignMismatch {
+        /// Block align written in the `fmt_` chunk
+        declared: u16,
+        /// Block align derived from the other `fmt_` fields
+        expected: u16,
+    },
+    /// The `data` chunk size was unknown or beyond the end of the file and has been clamped
+    DataSizeRepaired {
+        /// Size written in the `data` chunk header
+        declared: u32,
+        /// Number of bytes available for the chunk
+        actual: u32,
+    },
+    /// The `data` chunk ends with an incomplete frame, the trailing bytes are ignored
+    PartialFrame {
+        /// Number of bytes that don't make up a whole frame
+        trailing_bytes: usize,
+    },
+}
+
+/// Inconsistency found while parsing, located by its byte offset in the file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ParseWarning {
+    /// Offset of the offending field from the start of the file
+    pub offset: usize,
+    /// What is inconsistent
+    pub kind: ParseWarningKind,
+}
+
+impl fmt::Display for ParseWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarningKind::RiffSizeMismatch { declared, actual } => write!(
+                f,
+                "RIFF size of {} bytes but {} bytes follow",
+                declared, actual
+            ),
+            ParseWarningKind::ByteRateMismatch { declared, expected } => write!(
+                f,
+                "byte rate of {} but the format requires {}",
+                declared, expected
+            ),
+            ParseWarningKind::BlockAlignMismatch { declared, expected } => write!(
+                f,
+                "block align of {} but the format requires {}",
+                declared, expected
+            ),
+            ParseWarningKind::DataSizeRepaired { declared, actual } => write!(
+                f,
+                "`data` size of {} bytes clamped to the {} bytes available",
+                declared, actual
+            ),
+            ParseWarningKind::PartialFrame { trailing_bytes } => write!(
+                f,
+                "`data` ends with an incomplete frame of {} bytes",
+                trailing_bytes
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at byte offset {})", self.kind, self.offset)
+    }
+}
+
+/// Collects warnings or fails depending on the [`ParseMode`].
+pub(crate) struct Diagnostics {
+    mode: ParseMode,
+    pub(crate) warnings: Vec<ParseWarning>,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(options: &ParseOptions) -> Self {
+        Diagnostics {
+            mode: options.mode,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub(crate) fn report(&mut self, offset: usize, kind: ParseWarningKind) -> Result<(), Error> {
+        let warning = ParseWarning { offset, kind };
+
+        match self.mode {
+            ParseMode::Strict => Err(Error::Inconsistent(warning)),
+            ParseMode::Lenient | ParseMode::Recover => {
+                self.warnings.push(warning);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn recovers(&self) -> bool {
+        self.mode == ParseMode::Recover
+    }
+}
@@ -0,0 +1,199 @@
+use crate::data::Data;
+use crate::error::Error;
+use crate::wav::Wav;
+use alloc::vec::Vec;
+
+/// How to handle samples that exceed full scale after processing
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Overflow {
+    /// Clip samples to full scale, returning the number of clipped samples
+    #[default]
+    Clip,
+    /// Leave the samples untouched and fail with [`Error::Clipped`] if any sample would clip
+    Report,
+}
+
+/// Amplitude factor for a level in dB.
+pub(crate) fn db_to_gain(db: f64) -> f64 {
+    libm::pow(10.0, db / 20.0)
+}
+
+/// Level in dB for an amplitude factor.
+pub(crate) fn gain_to_db(gain: f64) -> f64 {
+    20.0 * libm::log10(gain)
+}
+
+impl Data {
+    /// Change the level by `db`, returning the number of clipped samples.
+    ///
+    /// ```
+    /// use wavv::{Data, Overflow};
+    ///
+    /// let mut data = Data::BitDepth16(vec![1000, -1000, 20_000]);
+    ///
+    /// assert_eq!(data.gain(6.0206, Overflow::Clip).unwrap(), 1);
+    /// assert_eq!(data, Data::BitDepth16(vec![2000, -2000, 32767]));
+    /// ```
+    pub fn gain(&mut self, db: f64, overflow: Overflow) -> Result<usize, Error> {
+        let gain = db_to_gain(db);
+
+        self.map(overflow, |samples| {
+            samples.iter_mut().for_each(|s| *s *= gain);
+        })
+    }
+
+    /// Change the level so the highest peak is at `db` dBFS, returning the change in dB.
+    ///
+    /// Silence is left untouched.
+    ///
+    /// ```
+    /// use wavv::Data;
+    ///
+    /// let mut data = Data::BitDepth16(vec![8192, -16384]);
+    /// let change = data.normalize(-1.0);
+    ///
+    /// assert_eq!(data, Data::BitDepth16(vec![14602, -29205]));
+    /// assert!((change - 5.0206).abs() < 0.001);
+    /// ```
+    pub fn normalize(&mut self, db: f64) -> f64 {
+        let peak = self
+            .to_f64_samples()
+            .iter()
+            .fold(0.0_f64, |peak, s| peak.max(s.abs()));
+
+        if peak == 0.0 {
+            return 0.0;
+        }
+
+        let change = db - gain_to_db(peak);
+
+        // Positive peaks are one step short of full scale, normalising to 0 dBFS may clip them
+        let _ = self.gain(change, Overflow::Clip);
+
+        change
+    }
+
+    /// Invert the polarity, the most negative sample is clipped to full scale.
+    pub fn invert(&mut self) -> usize {
+        self.map(Overflow::Clip, |samples| {
+            samples.iter_mut().for_each(|s| *s = -*s);
+        })
+        .unwrap_or(0)
+    }
+
+    /// Add the samples of `other`, converted to the bit depth of `self`.
+    ///
+    /// The result is as long as the longest of both, returns the number of clipped samples.
+    ///
+    /// ```
+    /// use wavv::{Data, Error, Overflow};
+    ///
+    /// let mut data = Data::BitDepth16(vec![30_000, 100]);
+    /// let other = Data::BitDepth16(vec![10_000, 100, 5]);
+    ///
+    /// assert_eq!(data.mix(&other, Overflow::Report), Err(Error::Clipped(1)));
+    /// assert_eq!(data.mix(&other, Overflow::Clip), Ok(1));
+    /// assert_eq!(data, Data::BitDepth16(vec![32767, 200, 5]));
+    /// ```
+    pub fn mix(&mut self, other: &Data, overflow: Overflow) -> Result<usize, Error> {
+        let other = other.to_f64_samples();
+
+        self.map(overflow, |samples| {
+            if other.len() > samples.len() {
+                samples.resize(other.len(), 0.0);
+            }
+
+            for (s, o) in samples.iter_mut().zip(&other) {
+                *s += o;
+            }
+        })
+    }
+
+    /// Process the samples scaled to `-1.0..1.0`, then convert them back.
+    fn map<F: FnOnce(&mut Vec<f64>)>(&mut self, overflow: Overflow, f: F) -> Result<usize, Error> {
+        let mut samples = self.to_f64_samples();
+
+        f(&mut samples);
+
+        let (data, clipped) = Data::from_f64_samples(&samples, self.bit_depth())?;
+
+        if overflow == Overflow::Report && clipped > 0 {
+            return Err(Error::Clipped(clipped));
+        }
+
+        *self = data;
+
+        Ok(clipped)
+    }
+}
+
+impl Wav {
+    /// Mix the samples of `other` into this file, see [`Data::mix`].
+    ///
+    /// `other` needs the same sample rate and number of channels.
+    pub fn mix(&mut self, other: &Wav, overflow: Overflow) -> Result<usize, Error> {
+        if other.fmt.sample_rate != self.fmt.sample_rate
+            || other.fmt.num_channels != self.fmt.num_channels
+        {
+            return Err(Error::IncompatibleData);
+        }
+
+        self.data.mix(&other.data, overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn gain_every_bit_depth() {
+        let mut data = Data::BitDepth8(vec![0, 64, 128, 192, 255]);
+
+        assert_eq!(data.gain(-6.0206, Overflow::Clip).unwrap(), 0);
+        assert_eq!(data, Data::BitDepth8(vec![64, 96, 128, 160, 191]));
+
+        let mut data = Data::BitDepth24(vec![-8_388_608, 4_194_304]);
+
+        assert_eq!(
+            data.gain(6.0206, Overflow::Report).unwrap_err(),
+            Error::Clipped(2)
+        );
+        assert_eq!(data, Data::BitDepth24(vec![-8_388_608, 4_194_304]));
+        assert_eq!(data.gain(6.0206, Overflow::Clip).unwrap(), 2);
+        assert_eq!(data, Data::BitDepth24(vec![-8_388_608, 8_388_607]));
+    }
+
+    #[test]
+    fn invert_polarity() {
+        let mut data = Data::BitDepth16(vec![-32768, -1, 0, 1000]);
+
+        assert_eq!(data.invert(), 1);
+        assert_eq!(data, Data::BitDepth16(vec![32767, 1, 0, -1000]));
+    }
+
+    #[test]
+    fn normalize_silence() {
+        let mut data = Data::BitDepth16(vec![0, 0]);
+
+        assert_eq!(data.normalize(-1.0), 0.0);
+        assert_eq!(data, Data::BitDepth16(vec![0, 0]));
+    }
+
+    #[test]
+    fn mix_wavs() {
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![100, 200]), 48_000, 2);
+        let other = Wav::from_data(Data::BitDepth8(vec![129, 127]), 48_000, 2);
+
+        assert_eq!(wav.mix(&other, Overflow::Report).unwrap(), 0);
+        assert_eq!(wav.data, Data::BitDepth16(vec![356, -56]));
+
+        let mono = Wav::from_data(Data::BitDepth16(vec![0]), 48_000, 1);
+
+        assert_eq!(
+            wav.mix(&mono, Overflow::Clip).unwrap_err(),
+            Error::IncompatibleData
+        );
+    }
+}
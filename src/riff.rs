@@ -0,0 +1,319 @@
+use crate::chunk::{Chunk, ChunkTag};
+use crate::error::Error;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Node in a RIFF tree, either a plain chunk or a `LIST` of nested nodes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    /// Chunk holding raw bytes
+    Chunk(Chunk),
+    /// `LIST` chunk holding nested nodes
+    List(List),
+}
+
+impl Node {
+    /// Tag of the chunk, [`ChunkTag::List`] for lists.
+    pub fn id(&self) -> ChunkTag {
+        match self {
+            Node::Chunk(chunk) => chunk.id,
+            Node::List(_) => ChunkTag::List,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Node::Chunk(chunk) => chunk.to_bytes(),
+            Node::List(list) => list.to_chunk().to_bytes(),
+        }
+    }
+}
+
+/// `LIST` chunk, a typed container of nested chunks and lists.
+#[derive(Debug, PartialEq, Clone)]
+pub struct List {
+    /// Type of the list, e.g. `INFO`, `adtl` or `movi`
+    pub list_type: ChunkTag,
+    /// Chunks and lists in the order they appear in the file
+    pub children: Vec<Node>,
+}
+
+impl List {
+    /// Parse the nested nodes of a `LIST` chunk.
+    ///
+    /// ```
+    /// use wavv::{Chunk, ChunkTag, List};
+    ///
+    /// let chunk = Chunk {
+    ///     id: ChunkTag::List,
+    ///     bytes: b"INFOINAM\x06\x00\x00\x00Title\x00".to_vec(),
+    /// };
+    ///
+    /// let list = List::from_chunk(&chunk).unwrap();
+    ///
+    /// assert_eq!(list.list_type, ChunkTag::from_bytes(b"INFO"));
+    /// assert_eq!(list.children.len(), 1);
+    /// assert_eq!(list.to_chunk(), chunk);
+    /// ```
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self, Error> {
+        let list_type = chunk.bytes.get(0..4).ok_or(Error::CantParseSliceInto {
+            tag: chunk.id,
+            offset: 0,
+        })?;
+
+        let list_type =
+            ChunkTag::from_bytes(&[list_type[0], list_type[1], list_type[2], list_type[3]]);
+        let children = parse_nodes(&chunk.bytes[4..]).map_err(|e| e.offset_by(4))?;
+
+        Ok(List {
+            list_type,
+            children,
+        })
+    }
+
+    /// Serialise the list and its children into a `LIST` chunk.
+    pub fn to_chunk(&self) -> Chunk {
+        let mut bytes = self.list_type.to_bytes().to_vec();
+
+        for child in &self.children {
+            bytes.extend_from_slice(&child.to_bytes());
+        }
+
+        Chunk {
+            id: ChunkTag::List,
+            bytes,
+        }
+    }
+
+    /// Find the first direct child chunk with the given tag.
+    pub fn find_chunk(&self, id: ChunkTag) -> Option<&Chunk> {
+        find_chunk(&self.children, id)
+    }
+
+    /// Find the first direct child list with the given type.
+    pub fn find_list(&self, list_type: ChunkTag) -> Option<&List> {
+        find_list(&self.children, list_type)
+    }
+}
+
+/// Generic RIFF file, the root of a tree of chunks and nested `LIST`s.
+///
+/// Works with any form type, e.g. `WAVE`, `AVI `, `sfbk`, `RMID` or `DLS `.
+///
+/// ```
+/// use std::fs;
+/// use std::path::Path;
+/// use wavv::{ChunkTag, Riff};
+///
+/// let bytes = fs::read(Path::new("./test_files/stereo_16_48000.wav")).unwrap();
+/// let riff = Riff::from_bytes(&bytes).unwrap();
+///
+/// assert_eq!(riff.form_type, ChunkTag::Wave);
+/// assert!(riff.find_chunk(ChunkTag::Fmt).is_some());
+/// assert_eq!(riff.to_bytes(), bytes);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Riff {
+    /// Form type following the RIFF header
+    pub form_type: ChunkTag,
+    /// Chunks and lists in the order they appear in the file
+    pub children: Vec<Node>,
+}
+
+impl Riff {
+    /// Parse a RIFF file into a tree, `LIST` chunks are parsed recursively.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let riff = Chunk::from_bytes(bytes)?;
+
+        if riff.id != ChunkTag::Riff {
+            return Err(Error::NoRiffChunkFound);
+        }
+
+        let form_type = riff.bytes.get(0..4).ok_or(Error::CantParseSliceInto {
+            tag: riff.id,
+            offset: 8,
+        })?;
+
+        let form_type =
+            ChunkTag::from_bytes(&[form_type[0], form_type[1], form_type[2], form_type[3]]);
+        let children = parse_nodes(&riff.bytes[4..]).map_err(|e| e.offset_by(12))?;
+
+        Ok(Riff {
+            form_type,
+            children,
+        })
+    }
+
+    /// Serialise the tree, sizes and padding bytes are written for every chunk.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        bytes.extend_from_slice(&ChunkTag::Riff.to_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0]); // chunk size (kept empty for later)
+        bytes.extend_from_slice(&self.form_type.to_bytes());
+
+        for child in &self.children {
+            bytes.extend_from_slice(&child.to_bytes());
+        }
+
+        // Subtract 8 for initial two words
+        let chunk_size = (bytes.len() as u32 - 8).to_le_bytes();
+        bytes[4..8].copy_from_slice(&chunk_size);
+
+        bytes
+    }
+
+    /// Find the first top level chunk with the given tag.
+    pub fn find_chunk(&self, id: ChunkTag) -> Option<&Chunk> {
+        find_chunk(&self.children, id)
+    }
+
+    /// Find the first top level list with the given type.
+    pub fn find_list(&self, list_type: ChunkTag) -> Option<&List> {
+        find_list(&self.children, list_type)
+    }
+}
+
+fn find_chunk(nodes: &[Node], id: ChunkTag) -> Option<&Chunk> {
+    nodes.iter().find_map(|node| match node {
+        Node::Chunk(chunk) if chunk.id == id => Some(chunk),
+        _ => None,
+    })
+}
+
+fn find_list(nodes: &[Node], list_type: ChunkTag) -> Option<&List> {
+    nodes.iter().find_map(|node| match node {
+        Node::List(list) if list.list_type == list_type => Some(list),
+        _ => None,
+    })
+}
+
+/// Parse consecutive chunks, recursing into `LIST`s.
+fn parse_nodes(bytes: &[u8]) -> Result<Vec<Node>, Error> {
+    let mut nodes = vec![];
+    let mut index = 0;
+
+    // Skip trailing bytes that can't hold another chunk header
+    while index + 8 <= bytes.len() {
+        let chunk = Chunk::from_bytes(&bytes[index..]).map_err(|e| e.offset_by(index))?;
+        let chunk_length = chunk.bytes.len();
+
+        let node = if chunk.id == ChunkTag::List {
+            List::from_chunk(&chunk)
+                .map(Node::List)
+                .map_err(|e| e.offset_by(index + 8))?
+        } else {
+            Node::Chunk(chunk)
+        };
+
+        nodes.push(node);
+
+        // Chunks should always have an even number of bytes,
+        // if it is odd there is an empty padding byte at the end
+        index += 8 + chunk_length + (chunk_length & 1);
+    }
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nested_lists() {
+        let bytes: [u8; 64] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x38, 0x00, 0x00, 0x00, // chunk size
+            0x41, 0x56, 0x49, 0x20, // AVI_
+            0x4c, 0x49, 0x53, 0x54, // LIST
+            0x22, 0x00, 0x00, 0x00, // chunk size
+            0x68, 0x64, 0x72, 0x6c, // hdrl
+            0x61, 0x76, 0x69, 0x68, // avih
+            0x02, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x02, // ...
+            0x4c, 0x49, 0x53, 0x54, // LIST
+            0x0c, 0x00, 0x00, 0x00, // chunk size
+            0x73, 0x74, 0x72, 0x6c, // strl
+            0x73, 0x74, 0x72, 0x68, // strh
+            0x00, 0x00, 0x00, 0x00, // chunk size
+            0x4a, 0x55, 0x4e, 0x4b, // JUNK
+            0x01, 0x00, 0x00, 0x00, // chunk size
+            0xaa, // ...
+            0x00, // padding byte
+        ];
+
+        let riff = Riff::from_bytes(&bytes).unwrap();
+
+        assert_eq!(riff.form_type, ChunkTag::from_bytes(b"AVI "));
+        assert_eq!(riff.children.len(), 2);
+
+        let hdrl = riff.find_list(ChunkTag::from_bytes(b"hdrl")).unwrap();
+
+        assert_eq!(
+            hdrl.find_chunk(ChunkTag::from_bytes(b"avih"))
+                .unwrap()
+                .bytes,
+            [0x01, 0x02]
+        );
+
+        let strl = hdrl.find_list(ChunkTag::from_bytes(b"strl")).unwrap();
+
+        assert_eq!(strl.children[0].id(), ChunkTag::from_bytes(b"strh"));
+        assert_eq!(riff.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn write_modified_tree() {
+        let mut riff = Riff {
+            form_type: ChunkTag::Wave,
+            children: vec![],
+        };
+
+        riff.children.push(Node::List(List {
+            list_type: ChunkTag::from_bytes(b"INFO"),
+            children: vec![Node::Chunk(Chunk {
+                id: ChunkTag::from_bytes(b"INAM"),
+                bytes: b"abc".to_vec(),
+            })],
+        }));
+
+        assert_eq!(
+            riff.to_bytes(),
+            [
+                0x52, 0x49, 0x46, 0x46, // RIFF
+                0x1c, 0x00, 0x00, 0x00, // chunk size
+                0x57, 0x41, 0x56, 0x45, // WAVE
+                0x4c, 0x49, 0x53, 0x54, // LIST
+                0x10, 0x00, 0x00, 0x00, // chunk size
+                0x49, 0x4e, 0x46, 0x4f, // INFO
+                0x49, 0x4e, 0x41, 0x4d, // INAM
+                0x03, 0x00, 0x00, 0x00, // chunk size
+                0x61, 0x62, 0x63, // abc
+                0x00, // padding byte
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_on_child_beyond_list() {
+        let bytes: [u8; 32] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x18, 0x00, 0x00, 0x00, // chunk size
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x4c, 0x49, 0x53, 0x54, // LIST
+            0x0c, 0x00, 0x00, 0x00, // chunk size
+            0x49, 0x4e, 0x46, 0x4f, // INFO
+            0x49, 0x4e, 0x41, 0x4d, // INAM
+            0x04, 0x00, 0x00, 0x00, // chunk size, beyond the end of the list
+        ];
+
+        assert_eq!(
+            Riff::from_bytes(&bytes).unwrap_err(),
+            Error::CantParseSliceInto {
+                tag: ChunkTag::from_bytes(b"INAM"),
+                offset: 32
+            }
+        );
+    }
+}
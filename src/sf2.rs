@@ -0,0 +1,421 @@
+use crate::chunk::{Chunk, ChunkTag};
+use crate::data::Data;
+use crate::error::Error;
+use crate::riff::{List, Riff};
+use crate::wav::Wav;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const SHDR_SIZE: usize = 46;
+
+/// Sample type flag for samples stored in ROM instead of the `smpl` chunk.
+const ROM_SAMPLE: u16 = 0x8000;
+
+/// Sample header from the `shdr` chunk of a SoundFont.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SampleHeader {
+    /// Name of the sample
+    pub name: String,
+    /// Index of the first sample in the sample data
+    pub start: u32,
+    /// Index of the first sample after the sample
+    pub end: u32,
+    /// Index of the first sample of the loop
+    pub loop_start: u32,
+    /// Index of the first sample after the loop
+    pub loop_end: u32,
+    /// Sample rate the sample was recorded at
+    pub sample_rate: u32,
+    /// MIDI key the sample was recorded at, `255` for unpitched sounds
+    pub original_pitch: u8,
+    /// Pitch correction in cents to apply on playback
+    pub pitch_correction: i8,
+    /// Index of the header of the other channel for stereo samples
+    pub sample_link: u16,
+    /// Mono (`1`), right (`2`), left (`4`) or linked (`8`), `0x8000` is set for ROM samples
+    pub sample_type: u16,
+}
+
+impl SampleHeader {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let u32_at =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+
+        SampleHeader {
+            name: parse_name(&bytes[0..20]),
+            start: u32_at(20),
+            end: u32_at(24),
+            loop_start: u32_at(28),
+            loop_end: u32_at(32),
+            sample_rate: u32_at(36),
+            original_pitch: bytes[40],
+            pitch_correction: bytes[41] as i8,
+            sample_link: u16_at(42),
+            sample_type: u16_at(44),
+        }
+    }
+}
+
+/// SoundFont 2 file (RIFF form `sfbk`), holding the sample data and headers.
+///
+/// Only the parts needed to extract samples are parsed, instruments and presets are ignored.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SoundFont {
+    /// Name of the SoundFont from the `INAM` chunk
+    pub name: String,
+    /// Major and minor version from the `ifil` chunk
+    pub version: (u16, u16),
+    /// Headers of every sample, without the terminal `EOS` record
+    pub samples: Vec<SampleHeader>,
+    /// 16 bit sample data from the `smpl` chunk
+    pub sample_data: Vec<i16>,
+    /// Least significant bytes of 24 bit sample data from the optional `sm24` chunk
+    pub sample_data_24: Option<Vec<u8>>,
+}
+
+impl SoundFont {
+    /// Parse a SoundFont 2 file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let riff = Riff::from_bytes(bytes)?;
+
+        if riff.form_type != tag(b"sfbk") {
+            return Err(Error::UnsupportedFormType(riff.form_type));
+        }
+
+        let info = find_list(&riff, b"INFO")?;
+        let sdta = find_list(&riff, b"sdta")?;
+        let pdta = find_list(&riff, b"pdta")?;
+
+        let version = match info.find_chunk(tag(b"ifil")) {
+            Some(chunk) => (chunk.read_u16(0)?, chunk.read_u16(2)?),
+            None => (0, 0),
+        };
+
+        let name = info
+            .find_chunk(tag(b"INAM"))
+            .map(|c| parse_name(&c.bytes))
+            .unwrap_or_default();
+
+        let sample_data = find_chunk(sdta, b"smpl")?
+            .bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+
+        // The 24 bit extension is only valid from version 2.04 on
+        let sample_data_24 = match sdta.find_chunk(tag(b"sm24")) {
+            Some(chunk) if version >= (2, 4) => Some(chunk.bytes.clone()),
+            _ => None,
+        };
+
+        let shdr = find_chunk(pdta, b"shdr")?;
+
+        if shdr.bytes.len() % SHDR_SIZE != 0 {
+            return Err(Error::CantParseChunk {
+                tag: shdr.id,
+                offset: shdr.bytes.len() - shdr.bytes.len() % SHDR_SIZE,
+            });
+        }
+
+        let mut samples: Vec<SampleHeader> = shdr
+            .bytes
+            .chunks_exact(SHDR_SIZE)
+            .map(SampleHeader::from_bytes)
+            .collect();
+
+        // Drop the terminal record
+        samples.pop();
+
+        Ok(SoundFont {
+            name,
+            version,
+            samples,
+            sample_data,
+            sample_data_24,
+        })
+    }
+
+    /// Export a sample as a mono [`Wav`].
+    ///
+    /// Samples are 24 bit when the SoundFont has an `sm24` chunk, 16 bit otherwise. The root key,
+    /// pitch correction and loop points are written to a sampler (`smpl`) chunk, invalid loops
+    /// and loops outside of the sample are dropped.
+    pub fn sample_to_wav(&self, header: &SampleHeader) -> Result<Wav, Error> {
+        let start = header.start as usize;
+        let end = header.end as usize;
+
+        if header.sample_type & ROM_SAMPLE != 0 || start > end || end > self.sample_data.len() {
+            let index = self.samples.iter().position(|h| h == header).unwrap_or(0);
+
+            return Err(Error::CantParseChunk {
+                tag: tag(b"shdr"),
+                offset: index * SHDR_SIZE,
+            });
+        }
+
+        let samples = &self.sample_data[start..end];
+
+        let data = match &self.sample_data_24 {
+            Some(lsb) if lsb.len() >= end => Data::BitDepth24(
+                samples
+                    .iter()
+                    .zip(&lsb[start..end])
+                    .map(|(s, l)| ((*s as i32) << 8) | *l as i32)
+                    .collect(),
+            ),
+            _ => Data::BitDepth16(samples.to_vec()),
+        };
+
+        let mut wav = Wav::from_data(data, header.sample_rate as usize, 1);
+
+        wav.chunks.push(sampler_chunk(header));
+
+        Ok(wav)
+    }
+
+    /// Export every sample as a mono [`Wav`], see [`SoundFont::sample_to_wav`].
+    pub fn to_wavs(&self) -> Result<Vec<Wav>, Error> {
+        self.samples.iter().map(|h| self.sample_to_wav(h)).collect()
+    }
+}
+
+/// Build a sampler chunk with the root key and a forward loop relative to the sample start.
+fn sampler_chunk(header: &SampleHeader) -> Chunk {
+    let sample_period = 1_000_000_000 / header.sample_rate.max(1);
+
+    // Unpitched samples play at middle C
+    let original_pitch = match header.original_pitch {
+        255 => 60,
+        pitch => pitch as i32,
+    };
+
+    // Players apply the correction, so the recorded pitch is the opposite in cents
+    let pitch = (original_pitch * 100 - header.pitch_correction as i32).clamp(0, 127 * 100 + 99);
+    let unity_note = (pitch / 100) as u32;
+    let pitch_fraction = (((pitch % 100) as u64) << 32) / 100;
+
+    let has_loop = header.loop_end > header.loop_start
+        && header.loop_start >= header.start
+        && header.loop_end <= header.end;
+
+    let mut bytes = vec![];
+
+    bytes.extend_from_slice(&0_u32.to_le_bytes()); // manufacturer
+    bytes.extend_from_slice(&0_u32.to_le_bytes()); // product
+    bytes.extend_from_slice(&sample_period.to_le_bytes()); // sample period
+    bytes.extend_from_slice(&unity_note.to_le_bytes()); // MIDI unity note
+    bytes.extend_from_slice(&(pitch_fraction as u32).to_le_bytes()); // MIDI pitch fraction
+    bytes.extend_from_slice(&0_u32.to_le_bytes()); // SMPTE format
+    bytes.extend_from_slice(&0_u32.to_le_bytes()); // SMPTE offset
+    bytes.extend_from_slice(&(has_loop as u32).to_le_bytes()); // num sample loops
+    bytes.extend_from_slice(&0_u32.to_le_bytes()); // sampler data
+
+    if has_loop {
+        let loop_start = header.loop_start - header.start;
+        // Sampler loops end on the last sample that is played
+        let loop_end = header.loop_end - header.start - 1;
+
+        bytes.extend_from_slice(&0_u32.to_le_bytes()); // cue point id
+        bytes.extend_from_slice(&0_u32.to_le_bytes()); // type, forward loop
+        bytes.extend_from_slice(&loop_start.to_le_bytes()); // start
+        bytes.extend_from_slice(&loop_end.to_le_bytes()); // end
+        bytes.extend_from_slice(&0_u32.to_le_bytes()); // fraction
+        bytes.extend_from_slice(&0_u32.to_le_bytes()); // play count, infinite
+    }
+
+    Chunk {
+        id: tag(b"smpl"),
+        bytes,
+    }
+}
+
+fn tag(bytes: &[u8; 4]) -> ChunkTag {
+    ChunkTag::from_bytes(bytes)
+}
+
+fn find_list<'a>(riff: &'a Riff, list_type: &[u8; 4]) -> Result<&'a List, Error> {
+    riff.find_list(tag(list_type))
+        .ok_or(Error::MissingChunk(tag(list_type)))
+}
+
+fn find_chunk<'a>(list: &'a List, id: &[u8; 4]) -> Result<&'a Chunk, Error> {
+    list.find_chunk(tag(id)).ok_or(Error::MissingChunk(tag(id)))
+}
+
+/// Parse a zero terminated ASCII string.
+fn parse_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riff::Node;
+
+    fn sample_header(name: &str, start: u32, end: u32, loop_start: u32, loop_end: u32) -> Vec<u8> {
+        let mut bytes = vec![0; SHDR_SIZE];
+
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes[20..24].copy_from_slice(&start.to_le_bytes());
+        bytes[24..28].copy_from_slice(&end.to_le_bytes());
+        bytes[28..32].copy_from_slice(&loop_start.to_le_bytes());
+        bytes[32..36].copy_from_slice(&loop_end.to_le_bytes());
+        bytes[36..40].copy_from_slice(&22_050_u32.to_le_bytes());
+        bytes[40] = 60; // original pitch
+        bytes[41] = -50_i8 as u8; // pitch correction
+        bytes[44] = 1; // mono
+
+        bytes
+    }
+
+    fn soundfont(sm24: bool) -> Vec<u8> {
+        let chunk = |id: &[u8; 4], bytes: Vec<u8>| Node::Chunk(Chunk { id: tag(id), bytes });
+        let list = |list_type: &[u8; 4], children: Vec<Node>| {
+            Node::List(List {
+                list_type: tag(list_type),
+                children,
+            })
+        };
+
+        let samples: Vec<u8> = [0_i16, 100, 200, 300, -100, -200, 0, 0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let mut sdta = vec![chunk(b"smpl", samples)];
+
+        if sm24 {
+            sdta.push(chunk(b"sm24", vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        }
+
+        let mut shdr = sample_header("first", 0, 4, 1, 3);
+        shdr.extend(sample_header("second", 4, 6, 0, 0));
+        shdr.extend(sample_header("EOS", 0, 0, 0, 0));
+
+        let riff = Riff {
+            form_type: tag(b"sfbk"),
+            children: vec![
+                list(
+                    b"INFO",
+                    vec![
+                        chunk(b"ifil", vec![2, 0, 4, 0]),
+                        chunk(b"INAM", b"Test font\0".to_vec()),
+                    ],
+                ),
+                list(b"sdta", sdta),
+                list(b"pdta", vec![chunk(b"shdr", shdr)]),
+            ],
+        };
+
+        riff.to_bytes()
+    }
+
+    #[test]
+    fn parse_soundfont() {
+        let sf = SoundFont::from_bytes(&soundfont(false)).unwrap();
+
+        assert_eq!(sf.name, "Test font");
+        assert_eq!(sf.version, (2, 4));
+        assert_eq!(sf.samples.len(), 2);
+        assert_eq!(sf.samples[1].name, "second");
+        assert_eq!(sf.samples[0].pitch_correction, -50);
+    }
+
+    #[test]
+    fn export_samples() {
+        let sf = SoundFont::from_bytes(&soundfont(false)).unwrap();
+        let wavs = sf.to_wavs().unwrap();
+
+        assert_eq!(wavs.len(), 2);
+        assert_eq!(wavs[0].fmt.sample_rate, 22_050);
+        assert_eq!(wavs[0].fmt.num_channels, 1);
+        assert_eq!(wavs[0].data, Data::BitDepth16(vec![0, 100, 200, 300]));
+        assert_eq!(wavs[1].data, Data::BitDepth16(vec![-100, -200]));
+
+        let smpl = &wavs[0].chunks[0];
+
+        assert_eq!(smpl.read_u32(8).unwrap(), 45_351); // sample period
+        assert_eq!(smpl.read_u32(12).unwrap(), 60); // unity note
+        assert_eq!(smpl.read_u32(16).unwrap(), 0x8000_0000); // pitch fraction
+        assert_eq!(smpl.read_u32(28).unwrap(), 1); // num loops
+        assert_eq!(smpl.read_u32(44).unwrap(), 1); // loop start
+        assert_eq!(smpl.read_u32(48).unwrap(), 2); // loop end
+
+        // Samples without a loop get no loop record
+        assert_eq!(wavs[1].chunks[0].read_u32(28).unwrap(), 0);
+        assert_eq!(wavs[1].chunks[0].bytes.len(), 36);
+    }
+
+    #[test]
+    fn drop_loops_outside_of_the_sample() {
+        let sf = SoundFont::from_bytes(&soundfont(false)).unwrap();
+
+        for (loop_start, loop_end) in [(1, 3), (1, 5), (5, 7), (6, 4)] {
+            let header =
+                SampleHeader::from_bytes(&sample_header("loop", 4, 6, loop_start, loop_end));
+            let wav = sf.sample_to_wav(&header).unwrap();
+
+            assert_eq!(wav.data, Data::BitDepth16(vec![-100, -200]));
+            assert_eq!(wav.chunks[0].read_u32(28).unwrap(), 0);
+        }
+
+        // Loops may span the whole sample
+        let header = SampleHeader::from_bytes(&sample_header("loop", 4, 6, 4, 6));
+        let smpl = &sf.sample_to_wav(&header).unwrap().chunks[0];
+
+        assert_eq!(smpl.read_u32(44).unwrap(), 0); // loop start
+        assert_eq!(smpl.read_u32(48).unwrap(), 1); // loop end
+    }
+
+    #[test]
+    fn unity_note_of_corrected_samples() {
+        let sf = SoundFont::from_bytes(&soundfont(false)).unwrap();
+        let unity_note = |original_pitch: u8, pitch_correction: i8| {
+            let mut bytes = sample_header("note", 4, 6, 0, 0);
+            bytes[40] = original_pitch;
+            bytes[41] = pitch_correction as u8;
+
+            let smpl = &sf
+                .sample_to_wav(&SampleHeader::from_bytes(&bytes))
+                .unwrap()
+                .chunks[0];
+
+            (smpl.read_u32(12).unwrap(), smpl.read_u32(16).unwrap())
+        };
+
+        assert_eq!(unity_note(60, 0), (60, 0));
+        assert_eq!(unity_note(60, 25), (59, 0xc000_0000));
+        assert_eq!(unity_note(255, 0), (60, 0));
+        assert_eq!(unity_note(0, 99), (0, 0));
+        assert_eq!(unity_note(127, -99), (127, 0xfd70_a3d7));
+        assert_eq!(unity_note(200, 0), (127, 0xfd70_a3d7));
+    }
+
+    #[test]
+    fn export_24_bit_samples() {
+        let sf = SoundFont::from_bytes(&soundfont(true)).unwrap();
+        let wav = sf.sample_to_wav(&sf.samples[1]).unwrap();
+
+        assert_eq!(
+            wav.data,
+            Data::BitDepth24(vec![-100 * 256 + 5, -200 * 256 + 6])
+        );
+    }
+
+    #[test]
+    fn reject_other_forms() {
+        let bytes = Riff {
+            form_type: ChunkTag::Wave,
+            children: vec![],
+        }
+        .to_bytes();
+
+        assert_eq!(
+            SoundFont::from_bytes(&bytes).unwrap_err(),
+            Error::UnsupportedFormType(ChunkTag::Wave)
+        );
+    }
+}
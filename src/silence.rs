@@ -0,0 +1,164 @@
+use crate::process::db_to_gain;
+use crate::wav::Wav;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::time::Duration;
+
+impl Wav {
+    /// Ranges of frames where every channel stays below `threshold` dBFS for at least
+    /// `min_duration`.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![0, 0, 0, 9000, 1, -1, 0, 9000]), 4, 1);
+    /// let regions = wav.silent_regions(-60.0, Duration::from_millis(750));
+    ///
+    /// assert_eq!(regions, [0..3, 4..7]);
+    /// ```
+    pub fn silent_regions(&self, threshold: f64, min_duration: Duration) -> Vec<Range<usize>> {
+        let num_channels = (self.fmt.num_channels as usize).max(1);
+        let threshold = db_to_gain(threshold);
+        let min_frames = self.fmt.time_to_frame(min_duration).max(1);
+
+        let mut regions = Vec::new();
+        let mut start = None;
+
+        let samples = self.data.to_f64_samples();
+        let frames = samples.chunks_exact(num_channels);
+        let num_frames = frames.len();
+
+        for (frame, samples) in frames.enumerate() {
+            let silent = samples.iter().all(|s| s.abs() < threshold);
+
+            match (silent, start) {
+                (true, None) => start = Some(frame),
+                (false, Some(s)) => {
+                    if frame - s >= min_frames {
+                        regions.push(s..frame);
+                    }
+
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(s) = start {
+            if num_frames - s >= min_frames {
+                regions.push(s..num_frames);
+            }
+        }
+
+        regions
+    }
+
+    /// New [`Wav`] without the silence below `threshold` dBFS at the start and end.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![0, 0, 9000, 0, 9000, 0]), 48_000, 1);
+    ///
+    /// assert_eq!(
+    ///     wav.trim_silence(-60.0).data,
+    ///     Data::BitDepth16(vec![9000, 0, 9000])
+    /// );
+    /// ```
+    pub fn trim_silence(&self, threshold: f64) -> Wav {
+        let num_frames = self.num_frames();
+        let regions = self.silent_regions(threshold, Duration::from_secs(0));
+
+        let start = match regions.first() {
+            Some(r) if r.start == 0 => r.end,
+            _ => 0,
+        };
+
+        let end = match regions.last() {
+            Some(r) if r.end == num_frames => r.start,
+            _ => num_frames,
+        };
+
+        self.slice(start..end.max(start))
+    }
+
+    /// Split into new [`Wav`]s at every silent region, see [`Wav::silent_regions`].
+    ///
+    /// The silent regions themselves are left out.
+    pub fn split_on_silence(&self, threshold: f64, min_duration: Duration) -> Vec<Wav> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+
+        for region in self.silent_regions(threshold, min_duration) {
+            if region.start > start {
+                parts.push(self.slice(start..region.start));
+            }
+
+            start = region.end;
+        }
+
+        if start < self.num_frames() {
+            parts.push(self.slice(start..self.num_frames()));
+        }
+
+        parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Data;
+    use alloc::vec;
+
+    #[test]
+    fn silence_on_every_channel() {
+        let wav = Wav::from_data(
+            Data::BitDepth8(vec![128, 128, 128, 200, 129, 127, 128, 128]),
+            10,
+            2,
+        );
+
+        assert_eq!(
+            wav.silent_regions(-40.0, Duration::from_secs(0)),
+            [0..1, 2..4]
+        );
+
+        let regions = wav.silent_regions(-40.0, Duration::from_millis(200));
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0], 2..4);
+
+        assert_eq!(
+            wav.silent_regions(-50.0, Duration::from_secs(0)),
+            [0..1, 3..4]
+        );
+    }
+
+    #[test]
+    fn split_on_silence() {
+        let wav = Wav::from_data(
+            Data::BitDepth16(vec![0, 500, 500, 0, 0, 0, 700, 0, 800, 0, 0]),
+            1000,
+            1,
+        );
+
+        let parts = wav.split_on_silence(-40.0, Duration::from_millis(2));
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].data, Data::BitDepth16(vec![0, 500, 500]));
+        assert_eq!(parts[1].data, Data::BitDepth16(vec![700, 0, 800]));
+    }
+
+    #[test]
+    fn trim_only_silence() {
+        let wav = Wav::from_data(Data::BitDepth24(vec![0, 0, 0]), 48_000, 1);
+
+        assert!(wav.trim_silence(-60.0).data.is_empty());
+
+        let wav = Wav::from_data(Data::BitDepth24(vec![100_000]), 48_000, 1);
+
+        assert_eq!(wav.trim_silence(-60.0).data, wav.data);
+    }
+}
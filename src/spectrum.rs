@@ -0,0 +1,388 @@
+use crate::data::Data;
+use crate::process::gain_to_db;
+use crate::wav::Wav;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+/// Window applied to every block of samples before the transform
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Window {
+    /// Good frequency resolution, sidelobes fall off from -31 dB
+    #[default]
+    Hann,
+    /// 4 term Blackman-Harris, wider main lobe with sidelobes below -92 dB
+    BlackmanHarris,
+}
+
+impl Window {
+    /// Periodic window of `size` samples.
+    fn coefficients(self, size: usize) -> Vec<f64> {
+        let terms: &[f64] = match self {
+            Window::Hann => &[0.5, 0.5],
+            Window::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+        };
+
+        (0..size)
+            .map(|n| {
+                let x = 2.0 * PI * n as f64 / size as f64;
+
+                terms
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| {
+                        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                        sign * a * libm::cos(k as f64 * x)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// Settings of a short-time Fourier transform, see [`Data::stft`]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Stft {
+    /// Window applied to every block
+    pub window: Window,
+    /// Number of samples per transform, rounded up to a power of two
+    pub size: usize,
+    /// Number of samples between the starts of consecutive blocks, at least 1
+    pub hop: usize,
+}
+
+impl Default for Stft {
+    fn default() -> Self {
+        Stft {
+            window: Window::Hann,
+            size: 4096,
+            hop: 1024,
+        }
+    }
+}
+
+/// Magnitude spectra of a single channel over time
+///
+/// Spectra hold linear magnitudes, scaled so a full scale sine wave centered on a bin has a
+/// magnitude of `1.0`. Every spectrum holds the `size / 2 + 1` bins from DC up to the Nyquist
+/// frequency, see [`Spectrogram::bin_frequency`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spectrogram {
+    /// Sample rate of the audio
+    pub sample_rate: u32,
+    /// Number of samples per transform
+    pub size: usize,
+    /// Number of samples between the starts of consecutive spectra
+    pub hop: usize,
+    /// Spectra in order of time
+    pub spectra: Vec<Vec<f64>>,
+}
+
+impl Spectrogram {
+    /// Center frequency of a bin in Hz.
+    pub fn bin_frequency(&self, bin: usize) -> f64 {
+        bin as f64 * self.sample_rate as f64 / self.size as f64
+    }
+
+    /// Root mean square of every bin over time.
+    pub fn average(&self) -> Vec<f64> {
+        let mut average = vec![0.0; self.size / 2 + 1];
+
+        for spectrum in &self.spectra {
+            for (sum, magnitude) in average.iter_mut().zip(spectrum) {
+                *sum += magnitude * magnitude;
+            }
+        }
+
+        let num_spectra = self.spectra.len().max(1) as f64;
+
+        average
+            .iter()
+            .map(|sum| libm::sqrt(sum / num_spectra))
+            .collect()
+    }
+
+    /// Highest frequency in Hz whose average magnitude is within `threshold` dB (e.g. `-60.0`)
+    /// of the strongest bin, `0.0` for silence.
+    ///
+    /// Audio that was upsampled or lossy encoded shows a bandwidth well below half the sample
+    /// rate.
+    pub fn bandwidth(&self, threshold: f64) -> f64 {
+        let average = self.average();
+        let strongest = average.iter().cloned().fold(0.0, f64::max);
+
+        if strongest == 0.0 {
+            return 0.0;
+        }
+
+        average
+            .iter()
+            .rposition(|magnitude| gain_to_db(magnitude / strongest) >= threshold)
+            .map_or(0.0, |bin| self.bin_frequency(bin))
+    }
+}
+
+impl Data {
+    /// Magnitude spectra of a single channel from interleaved samples, see [`Spectrogram`].
+    ///
+    /// Blocks start every `hop` samples as long as they fit, a channel shorter than a single
+    /// block is padded with silence. There are no spectra for channels that don't exist.
+    ///
+    /// ```
+    /// use wavv::{Data, Stft};
+    ///
+    /// // Full scale sine at a quarter of the sample rate
+    /// let data = Data::BitDepth16(vec![0, 32767, 0, -32767].repeat(64));
+    /// let stft = Stft { size: 64, hop: 64, ..Stft::default() };
+    ///
+    /// let spectra = data.stft(1, 0, &stft);
+    ///
+    /// assert_eq!(spectra.len(), 4);
+    /// assert!((spectra[0][16] - 1.0).abs() < 0.001);
+    /// assert!(spectra[0][0] < 0.001);
+    /// ```
+    pub fn stft(&self, num_channels: usize, channel: usize, stft: &Stft) -> Vec<Vec<f64>> {
+        if channel >= num_channels {
+            return vec![];
+        }
+
+        let size = stft.size.max(2).next_power_of_two();
+        let hop = stft.hop.max(1);
+        let samples = self.channel(channel, num_channels).to_f64_samples();
+
+        let window = stft.window.coefficients(size);
+        let scale = 2.0 / window.iter().sum::<f64>();
+
+        let mut starts: Vec<usize> = (0..samples.len()).step_by(hop).collect();
+        starts.retain(|start| start + size <= samples.len() || *start == 0);
+
+        let mut spectra = vec![];
+        let mut real = vec![0.0; size];
+        let mut imaginary = vec![0.0; size];
+
+        for start in starts {
+            for (n, w) in window.iter().enumerate() {
+                real[n] = samples.get(start + n).map_or(0.0, |s| s * w);
+                imaginary[n] = 0.0;
+            }
+
+            fft(&mut real, &mut imaginary);
+
+            let spectrum = (0..=size / 2)
+                .map(|k| {
+                    // DC and Nyquist have no mirrored negative frequency
+                    let bin_scale = if k == 0 || k == size / 2 {
+                        scale / 2.0
+                    } else {
+                        scale
+                    };
+                    libm::sqrt(real[k] * real[k] + imaginary[k] * imaginary[k]) * bin_scale
+                })
+                .collect();
+
+            spectra.push(spectrum);
+        }
+
+        spectra
+    }
+}
+
+impl Wav {
+    /// Magnitude spectra of a single channel over time, see [`Data::stft`].
+    pub fn spectrogram(&self, channel: usize, stft: &Stft) -> Spectrogram {
+        let size = stft.size.max(2).next_power_of_two();
+
+        Spectrogram {
+            sample_rate: self.fmt.sample_rate,
+            size,
+            hop: stft.hop.max(1),
+            spectra: self
+                .data
+                .stft(self.fmt.num_channels as usize, channel, stft),
+        }
+    }
+
+    /// Highest bandwidth of any channel in Hz, see [`Spectrogram::bandwidth`].
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, Signal, Stft, Wav};
+    ///
+    /// let fmt = Wav::from_data(Data::BitDepth16(vec![]), 48_000, 1).fmt;
+    /// let noise = Wav::generate(&fmt, Signal::WhiteNoise(1), Duration::from_secs(1), 0.0).unwrap();
+    ///
+    /// assert!(noise.bandwidth(&Stft::default(), -40.0) > 23_000.0);
+    /// ```
+    pub fn bandwidth(&self, stft: &Stft, threshold: f64) -> f64 {
+        (0..self.fmt.num_channels as usize)
+            .map(|channel| self.spectrogram(channel, stft).bandwidth(threshold))
+            .fold(0.0, f64::max)
+    }
+}
+
+/// In-place iterative radix-2 FFT, the length needs to be a power of two.
+fn fft(real: &mut [f64], imaginary: &mut [f64]) {
+    let n = real.len();
+
+    // Bit reversal permutation
+    let mut j = 0;
+
+    for i in 1..n {
+        let mut bit = n >> 1;
+
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+
+        j |= bit;
+
+        if i < j {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+
+    while length <= n {
+        let angle = -2.0 * PI / length as f64;
+
+        for k in 0..length / 2 {
+            let (w_im, w_re) = (libm::sin(angle * k as f64), libm::cos(angle * k as f64));
+
+            for start in (0..n).step_by(length) {
+                let (a, b) = (start + k, start + k + length / 2);
+
+                let t_re = real[b] * w_re - imaginary[b] * w_im;
+                let t_im = real[b] * w_im + imaginary[b] * w_re;
+
+                real[b] = real[a] - t_re;
+                imaginary[b] = imaginary[a] - t_im;
+                real[a] += t_re;
+                imaginary[a] += t_im;
+            }
+        }
+
+        length <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::Signal;
+    use core::time::Duration;
+
+    fn fmt(sample_rate: usize, num_channels: usize) -> crate::fmt::Fmt {
+        Wav::from_data(Data::BitDepth24(vec![]), sample_rate, num_channels).fmt
+    }
+
+    #[test]
+    fn fft_matches_dft() {
+        let input: Vec<f64> = (0..16)
+            .map(|i| libm::sin(i as f64 * 1.3) + i as f64 * 0.1)
+            .collect();
+
+        let mut real = input.clone();
+        let mut imaginary = vec![0.0; 16];
+        fft(&mut real, &mut imaginary);
+
+        for k in 0..16 {
+            let (mut re, mut im) = (0.0, 0.0);
+
+            for (n, x) in input.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f64 / 16.0;
+                re += x * libm::cos(angle);
+                im += x * libm::sin(angle);
+            }
+
+            assert!((real[k] - re).abs() < 1e-9);
+            assert!((imaginary[k] - im).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn hum_on_second_channel() {
+        let mut wav = Wav::generate(
+            &fmt(8000, 2),
+            Signal::Sine(50.0),
+            Duration::from_secs(2),
+            -20.0,
+        )
+        .unwrap();
+
+        // Silence the first channel
+        let right = wav.data.channel(1, 2).to_f64_samples();
+        let samples: Vec<f64> = right.iter().flat_map(|s| [0.0, *s]).collect();
+        wav.data = Data::from_f64_samples(&samples, 24).unwrap().0;
+
+        let stft = Stft {
+            window: Window::BlackmanHarris,
+            size: 8000,
+            hop: 4096,
+        };
+
+        let left = wav.spectrogram(0, &stft);
+        let right = wav.spectrogram(1, &stft);
+
+        assert_eq!(right.size, 8192);
+        assert_eq!(right.spectra.len(), 2);
+        assert!(left.average().iter().all(|m| *m == 0.0));
+        assert_eq!(left.bandwidth(-60.0), 0.0);
+
+        let average = right.average();
+        let strongest = (0..average.len())
+            .max_by(|a, b| average[*a].total_cmp(&average[*b]))
+            .unwrap();
+
+        assert!((right.bin_frequency(strongest) - 50.0).abs() < 1.0);
+
+        // Within the scalloping loss of the window
+        assert!((gain_to_db(average[strongest]) + 20.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn bandwidth_of_a_tone() {
+        let wav = Wav::generate(
+            &fmt(48_000, 1),
+            Signal::Sine(3100.0),
+            Duration::from_millis(500),
+            -6.0,
+        )
+        .unwrap();
+
+        let hann = Stft::default();
+        let blackman_harris = Stft {
+            window: Window::BlackmanHarris,
+            ..hann
+        };
+
+        let bandwidth = wav.bandwidth(&blackman_harris, -80.0);
+        assert!(bandwidth > 3100.0 && bandwidth < 3200.0);
+
+        // Hann leaks further from the tone
+        assert!(wav.bandwidth(&hann, -80.0) > bandwidth);
+    }
+
+    #[test]
+    fn short_channel_is_padded() {
+        let data = Data::BitDepth8(vec![255, 128, 128]);
+        let spectra = data.stft(1, 0, &Stft::default());
+
+        assert_eq!(spectra.len(), 1);
+        assert_eq!(spectra[0].len(), 2049);
+        assert!(Data::BitDepth8(vec![])
+            .stft(1, 0, &Stft::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn missing_channel_has_no_spectra() {
+        let wav = Wav::from_data(Data::BitDepth16([1000, -1000].repeat(64)), 48_000, 2);
+
+        assert!(wav.spectrogram(2, &Stft::default()).spectra.is_empty());
+        assert!(wav.data.stft(0, 0, &Stft::default()).is_empty());
+        assert_eq!(wav.spectrogram(1, &Stft::default()).spectra.len(), 1);
+    }
+}
@@ -1,11 +1,17 @@
-use crate::chunk::{parse_chunks, Chunk, ChunkTag};
+use crate::chunk::{parse_chunks_with_offsets, Chunk, ChunkTag};
 use crate::data::Data;
 use crate::error::Error;
-use crate::fmt::Fmt;
+use crate::fmt::{AudioFormat, Fmt};
+use crate::ima_adpcm;
+use crate::ms_adpcm;
+use crate::parse::{Diagnostics, ParseOptions, ParseWarning, ParseWarningKind};
+use crate::write::WriteOptions;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::time::Duration;
 
 /// Struct representing a WAV file
+#[derive(Debug, Clone)]
 pub struct Wav {
     /// Contains data from the fmt chunk / header part of the file
     pub fmt: Fmt,
@@ -31,28 +37,153 @@ impl Wav {
     /// assert_eq!(wav.fmt.sample_rate, 48_000);
     /// ```
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
-        let parsed_chunks = parse_chunks(bytes)?;
+        Wav::from_bytes_with_options(bytes, &ParseOptions::lenient()).map(|(wav, _)| wav)
+    }
+
+    /// Create new [`Wav`] instance from a slice of bytes, checking the headers for consistency.
+    ///
+    /// In [`ParseMode::Lenient`](crate::ParseMode::Lenient) every inconsistency is returned as a
+    /// [`ParseWarning`], in [`ParseMode::Strict`](crate::ParseMode::Strict) the first one is
+    /// returned as [`Error::Inconsistent`].
+    ///
+    /// ```
+    /// use wavv::{Error, ParseOptions, ParseWarningKind, Wav};
+    ///
+    /// let bytes: [u8; 48] = [
+    ///     0x52, 0x49, 0x46, 0x46, // RIFF
+    ///     0x28, 0x00, 0x00, 0x00, // chunk size
+    ///     0x57, 0x41, 0x56, 0x45, // WAVE
+    ///     0x66, 0x6d, 0x74, 0x20, // fmt_
+    ///     0x10, 0x00, 0x00, 0x00, // chunk size
+    ///     0x01, 0x00, // audio format
+    ///     0x01, 0x00, // num channels
+    ///     0x80, 0xbb, 0x00, 0x00, // sample rate
+    ///     0x00, 0x00, 0x00, 0x00, // byte rate (should be 96_000)
+    ///     0x02, 0x00, // block align
+    ///     0x10, 0x00, // bits per sample
+    ///     0x64, 0x61, 0x74, 0x61, // data
+    ///     0x04, 0x00, 0x00, 0x00, // chunk size
+    ///     0x01, 0x00, 0x02, 0x00, // samples
+    /// ];
+    ///
+    /// let (wav, warnings) = Wav::from_bytes_with_options(&bytes, &ParseOptions::lenient()).unwrap();
+    ///
+    /// assert_eq!(wav.data.len(), 2);
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(warnings[0].offset, 28);
+    /// assert_eq!(
+    ///     warnings[0].kind,
+    ///     ParseWarningKind::ByteRateMismatch { declared: 0, expected: 96_000 }
+    /// );
+    ///
+    /// let error = Wav::from_bytes_with_options(&bytes, &ParseOptions::strict()).unwrap_err();
+    ///
+    /// assert_eq!(error, Error::Inconsistent(warnings[0]));
+    /// ```
+    pub fn from_bytes_with_options(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<(Self, Vec<ParseWarning>), Error> {
+        let mut diagnostics = Diagnostics::new(options);
+        let parsed_chunks = parse_chunks_with_offsets(bytes, &mut diagnostics)?;
+
+        let (fmt_offset, fmt_chunk) = parsed_chunks
+            .iter()
+            .find(|(_, c)| c.id == ChunkTag::Fmt)
+            .ok_or(Error::NoFmtChunkFound)?;
+
+        let fmt = Fmt::from_chunk(fmt_chunk).map_err(|e| e.offset_by(fmt_offset + 8))?;
+
+        let declared = fmt_chunk.read_u32(8)?;
+        let expected = fmt.byte_rate();
+
+        // The byte rate of compressed formats is an average that encoders round differently
+        if fmt.format == AudioFormat::Pcm && declared != expected {
+            diagnostics.report(
+                fmt_offset + 16,
+                ParseWarningKind::ByteRateMismatch { declared, expected },
+            )?;
+        }
+
+        let block_align = fmt_chunk.read_u16(12)?;
+        let expected = fmt.block_align();
+
+        if block_align != expected {
+            diagnostics.report(
+                fmt_offset + 20,
+                ParseWarningKind::BlockAlignMismatch {
+                    declared: block_align,
+                    expected,
+                },
+            )?;
+        }
 
-        let fmt = parsed_chunks
+        let (data_offset, data_chunk) = parsed_chunks
             .iter()
-            .find(|c| c.id == ChunkTag::Fmt)
-            .ok_or(Error::NoFmtChunkFound)
-            .and_then(|c| Fmt::from_chunk(&c))?;
+            .find(|(_, c)| c.id == ChunkTag::Data)
+            .ok_or(Error::NoDataChunkFound)?;
 
-        let data = parsed_chunks
+        let num_frames = parsed_chunks
             .iter()
-            .find(|c| c.id == ChunkTag::Data)
-            .ok_or(Error::NoDataChunkFound)
-            .and_then(|c| Data::from_chunk(&fmt, &c))?;
+            .find(|(_, c)| c.id == ChunkTag::Fact)
+            .and_then(|(_, c)| c.read_u32(0).ok())
+            .map(|frames| frames as usize);
+
+        let data = match fmt.format {
+            AudioFormat::Pcm => {
+                let frame_size = expected as usize;
+                let trailing_bytes = data_chunk.bytes.len() % frame_size.max(1);
+
+                if trailing_bytes != 0 {
+                    diagnostics.report(
+                        data_offset + 4,
+                        ParseWarningKind::PartialFrame { trailing_bytes },
+                    )?;
+                }
+
+                let whole_frames = Chunk {
+                    id: ChunkTag::Data,
+                    bytes: data_chunk.bytes[..data_chunk.bytes.len() - trailing_bytes].to_vec(),
+                };
+
+                Data::from_chunk(&fmt, &whole_frames).map_err(|e| e.offset_by(data_offset + 8))?
+            }
+            AudioFormat::ImaAdpcm { samples_per_block } => {
+                let samples = ima_adpcm::decode(
+                    &data_chunk.bytes,
+                    fmt.num_channels as usize,
+                    samples_per_block as usize,
+                    num_frames,
+                )
+                .map_err(|e| e.offset_by(data_offset + 8))?;
+
+                Data::BitDepth16(samples)
+            }
+            AudioFormat::MsAdpcm { .. } => {
+                // Encoders differ in how they round the block size, so use the declared one
+                let samples = ms_adpcm::decode(
+                    &data_chunk.bytes,
+                    fmt.num_channels as usize,
+                    block_align as usize,
+                    &fmt.extra,
+                    num_frames,
+                )
+                .map_err(|e| e.offset_by(data_offset + 8))?;
+
+                Data::BitDepth16(samples)
+            }
+        };
 
         let chunks = parsed_chunks
             .into_iter()
-            .filter(|c| c.id != ChunkTag::Data && c.id != ChunkTag::Fmt)
+            .map(|(_, c)| c)
+            .filter(|c| c.id != ChunkTag::Data && c.id != ChunkTag::Fmt && c.id != ChunkTag::Fact)
+            .filter(|c| !c.id.is_filler())
             .collect();
 
         let wave = Wav { data, fmt, chunks };
 
-        Ok(wave)
+        Ok((wave, diagnostics.warnings))
     }
 
     /// Create a [`Wav`] struct from samples.
@@ -75,9 +206,11 @@ impl Wav {
         };
 
         let fmt = Fmt {
+            format: AudioFormat::Pcm,
             sample_rate: sample_rate as u32,
             num_channels: num_channels as u16,
             bit_depth,
+            extra: vec![],
         };
 
         Wav {
@@ -89,6 +222,11 @@ impl Wav {
 
     /// Convert a [`Wav`] instance into bytes.
     ///
+    /// Samples are encoded according to `fmt.format`, for compressed formats a `fact` chunk
+    /// holding the number of frames is written as well. Formats that can't be encoded are
+    /// written as 16 bit PCM. Other chunks are written in order
+    /// between the `fmt_` and `data` chunks.
+    ///
     /// Useful if you have raw sample data that you want to convert to a .wav file:
     ///
     /// ```
@@ -117,6 +255,28 @@ impl Wav {
     /// assert_eq!(wav.to_bytes(), bytes);
     /// ```
     pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_with_options(&WriteOptions::default())
+    }
+
+    /// Convert a [`Wav`] struct into a vector of bytes, reserving space in front of the
+    /// sample data.
+    ///
+    /// ```
+    /// use wavv::{Data, Wav, WriteOptions};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![0; 16]), 48_000, 2);
+    /// let options = WriteOptions {
+    ///     reserve: 28,
+    ///     align: 512,
+    /// };
+    ///
+    /// let bytes = wav.to_bytes_with_options(&options);
+    ///
+    /// assert_eq!(&bytes[12..16], b"JUNK");
+    /// assert_eq!(&bytes[504..508], b"data");
+    /// assert_eq!(Wav::from_bytes(&bytes).unwrap().chunks, []);
+    /// ```
+    pub fn to_bytes_with_options(&self, options: &WriteOptions) -> Vec<u8> {
         let mut bytes = vec![
             0x52, 0x49, 0x46, 0x46, // RIFF
             0x00, 0x00, 0x00, 0x00, // chunk size (kept empty for later)
@@ -124,7 +284,51 @@ impl Wav {
         ];
 
         bytes.extend_from_slice(&self.fmt.to_chunk().to_bytes());
-        bytes.extend_from_slice(&self.data.to_chunk().to_bytes());
+
+        let data = match self.fmt.format {
+            AudioFormat::Pcm => self.data.to_chunk(),
+            AudioFormat::ImaAdpcm { samples_per_block }
+                if ima_adpcm::is_valid_samples_per_block(samples_per_block as usize) =>
+            {
+                let num_channels = self.fmt.num_channels as usize;
+                let samples = self.data.to_i16_samples();
+                let num_frames = (samples.len() / num_channels.max(1)) as u32;
+
+                let fact = Chunk {
+                    id: ChunkTag::Fact,
+                    bytes: num_frames.to_le_bytes().to_vec(),
+                };
+
+                bytes.extend_from_slice(&fact.to_bytes());
+
+                Chunk {
+                    id: ChunkTag::Data,
+                    bytes: ima_adpcm::encode(&samples, num_channels, samples_per_block as usize),
+                }
+            }
+            // Matches the PCM fmt chunk written for formats that can't be encoded
+            AudioFormat::ImaAdpcm { .. } | AudioFormat::MsAdpcm { .. } => {
+                Data::BitDepth16(self.data.to_i16_samples()).to_chunk()
+            }
+        };
+
+        // Keep the sample data last so it can be appended to
+        for chunk in &self.chunks {
+            bytes.extend_from_slice(&chunk.to_bytes());
+        }
+
+        let junk_size = options.junk_size(bytes.len());
+
+        if junk_size > 0 {
+            let junk = Chunk {
+                id: ChunkTag::Junk,
+                bytes: vec![0; junk_size - 8],
+            };
+
+            bytes.splice(12..12, junk.to_bytes());
+        }
+
+        bytes.extend_from_slice(&data.to_bytes());
 
         // Subtract 8 for initial two words
         let chunk_size = (bytes.len() as u32 - 8).to_le_bytes();
@@ -136,6 +340,124 @@ impl Wav {
 
         bytes
     }
+
+    /// Number of frames, a frame holding one sample for every channel.
+    pub fn num_frames(&self) -> usize {
+        self.fmt.sample_to_frame(self.data.len())
+    }
+
+    /// Playing time of the samples.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16(vec![0; 96_000]), 48_000, 2);
+    ///
+    /// assert_eq!(wav.num_frames(), 48_000);
+    /// assert_eq!(wav.duration(), Duration::from_secs(1));
+    /// assert_eq!(wav.duration_secs(), 1.0);
+    /// ```
+    pub fn duration(&self) -> Duration {
+        self.fmt.frame_to_time(self.num_frames())
+    }
+
+    /// Playing time of the samples in seconds.
+    pub fn duration_secs(&self) -> f64 {
+        match self.fmt.sample_rate {
+            0 => 0.0,
+            rate => self.num_frames() as f64 / rate as f64,
+        }
+    }
+
+    /// New [`Wav`] holding the frames between `start` and `end`, see [`Wav::slice`].
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use wavv::{Data, Wav};
+    ///
+    /// let wav = Wav::from_data(Data::BitDepth16((0..8).collect()), 4, 2);
+    /// let slice = wav.slice_time(Duration::from_millis(250), Duration::from_millis(750));
+    ///
+    /// assert_eq!(slice.data, Data::BitDepth16(vec![2, 3, 4, 5]));
+    /// ```
+    pub fn slice_time(&self, start: Duration, end: Duration) -> Self {
+        self.slice(self.fmt.time_to_frame(start)..self.fmt.time_to_frame(end))
+    }
+
+    /// Repair a truncated file or a file with streaming headers, producing a valid file.
+    ///
+    /// Chunks are copied as they are, except for the `data` chunk which is clamped to the
+    /// available bytes and trimmed to whole frames. The RIFF and chunk sizes are rewritten to
+    /// match, sample bytes are never decoded and re-encoded. Returns the repaired file along
+    /// with a [`ParseWarning`] for everything that was repaired.
+    ///
+    /// ```
+    /// use wavv::{ParseWarningKind, Wav};
+    ///
+    /// let bytes: [u8; 49] = [
+    ///     0x52, 0x49, 0x46, 0x46, // RIFF
+    ///     0xff, 0xff, 0xff, 0xff, // chunk size, unknown while streaming
+    ///     0x57, 0x41, 0x56, 0x45, // WAVE
+    ///     0x66, 0x6d, 0x74, 0x20, // fmt_
+    ///     0x10, 0x00, 0x00, 0x00, // chunk size
+    ///     0x01, 0x00, // audio format
+    ///     0x01, 0x00, // num channels
+    ///     0x80, 0xbb, 0x00, 0x00, // sample rate
+    ///     0x00, 0x77, 0x01, 0x00, // byte rate
+    ///     0x02, 0x00, // block align
+    ///     0x10, 0x00, // bits per sample
+    ///     0x64, 0x61, 0x74, 0x61, // data
+    ///     0x00, 0x00, 0x00, 0x00, // chunk size, unknown while streaming
+    ///     0x01, 0x00, 0x02, 0x00, // samples
+    ///     0x03, // incomplete sample
+    /// ];
+    ///
+    /// let (repaired, warnings) = Wav::repair_bytes(&bytes).unwrap();
+    ///
+    /// assert_eq!(repaired.len(), 48);
+    /// assert_eq!(
+    ///     warnings[1].kind,
+    ///     ParseWarningKind::DataSizeRepaired { declared: 0, actual: 5 }
+    /// );
+    ///
+    /// let (wav, warnings) = Wav::from_bytes_with_options(&repaired, &Default::default()).unwrap();
+    ///
+    /// assert!(warnings.is_empty());
+    /// assert_eq!(wav.data.len(), 2);
+    /// ```
+    pub fn repair_bytes(bytes: &[u8]) -> Result<(Vec<u8>, Vec<ParseWarning>), Error> {
+        let options = ParseOptions::recover();
+        let (wav, warnings) = Wav::from_bytes_with_options(bytes, &options)?;
+        let chunks = parse_chunks_with_offsets(bytes, &mut Diagnostics::new(&options))?;
+
+        // Compressed blocks can be decoded partially, so only PCM is trimmed
+        let frame_size = match wav.fmt.format {
+            AudioFormat::Pcm => wav.fmt.block_align().max(1) as usize,
+            _ => 1,
+        };
+
+        let mut repaired = vec![
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x00, 0x00, 0x00, 0x00, // chunk size (kept empty for later)
+            0x57, 0x41, 0x56, 0x45, // WAVE
+        ];
+
+        for (_, mut chunk) in chunks {
+            if chunk.id == ChunkTag::Data {
+                let whole_frames = chunk.bytes.len() - chunk.bytes.len() % frame_size;
+                chunk.bytes.truncate(whole_frames);
+            }
+
+            repaired.extend_from_slice(&chunk.to_bytes());
+        }
+
+        // Subtract 8 for initial two words
+        let chunk_size = (repaired.len() as u32 - 8).to_le_bytes();
+        repaired[4..8].copy_from_slice(&chunk_size);
+
+        Ok((repaired, warnings))
+    }
 }
 
 #[cfg(test)]
@@ -307,6 +629,37 @@ mod tests {
         assert_eq!(wave.to_bytes(), bytes);
     }
 
+    #[test]
+    fn write_chunks_before_data() {
+        let mut wav = Wav::from_data(Data::BitDepth16(vec![1, -1]), 48_000, 1);
+        wav.chunks.push(Chunk {
+            id: ChunkTag::from_bytes(b"iXML"),
+            bytes: b"<a/>".to_vec(),
+        });
+        wav.chunks.push(Chunk {
+            id: ChunkTag::from_bytes(b"bext"),
+            bytes: vec![1, 2, 3],
+        });
+
+        let bytes = wav.to_bytes();
+
+        assert_eq!(bytes.len(), 12 + 24 + 12 + 12 + 12);
+        assert_eq!(
+            bytes[36..48],
+            [b'i', b'X', b'M', b'L', 4, 0, 0, 0, b'<', b'a', b'/', b'>']
+        );
+        assert_eq!(
+            bytes[48..60],
+            [b'b', b'e', b'x', b't', 3, 0, 0, 0, 1, 2, 3, 0]
+        );
+        assert_eq!(bytes[60..64], *b"data");
+
+        let parsed = Wav::from_bytes(&bytes).unwrap();
+
+        assert_eq!(parsed.chunks, wav.chunks);
+        assert_eq!(parsed.data, wav.data);
+    }
+
     #[test]
     fn parse_files() {
         let bytes = fs::read(Path::new("./test_files/mono_16_48000.wav")).unwrap();
@@ -337,4 +690,212 @@ mod tests {
         assert_eq!(wav.fmt.bit_depth, 24);
         assert_eq!(wav.fmt.sample_rate, 48_000);
     }
+
+    #[test]
+    fn ima_adpcm_from_and_to_bytes() {
+        let samples: Vec<i16> = (0..1000).map(|i| ((i * 37) % 4000) as i16 - 2000).collect();
+        let mut wav = Wav::from_data(Data::BitDepth16(samples), 8_000, 1);
+
+        wav.fmt.format = AudioFormat::ImaAdpcm {
+            samples_per_block: 505,
+        };
+
+        let bytes = wav.to_bytes();
+
+        // RIFF header + fmt_ (20 bytes) + fact + 2 blocks of 256 bytes
+        assert_eq!(bytes.len(), 12 + 28 + 12 + 8 + 512);
+
+        let parsed = Wav::from_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            parsed.fmt.format,
+            AudioFormat::ImaAdpcm {
+                samples_per_block: 505
+            }
+        );
+        assert_eq!(parsed.fmt.bit_depth, 16);
+        assert_eq!(parsed.fmt.bit_depth, parsed.data.bit_depth());
+        assert_eq!(parsed.data.len(), 1000);
+        assert!(parsed.chunks.is_empty());
+        assert_eq!(parsed.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn invalid_ima_adpcm_is_written_as_pcm() {
+        let samples: Vec<i16> = (0..100).map(|i| i * 100 - 5000).collect();
+
+        for samples_per_block in [0, 12] {
+            let mut wav = Wav::from_data(Data::BitDepth16(samples.clone()), 8_000, 2);
+            wav.fmt.format = AudioFormat::ImaAdpcm { samples_per_block };
+
+            let written = Wav::from_bytes(&wav.to_bytes()).unwrap();
+
+            assert_eq!(written.fmt.format, AudioFormat::Pcm);
+            assert_eq!(written.data, wav.data);
+        }
+    }
+
+    #[test]
+    fn parse_many_channels() {
+        let bytes: [u8; 44] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x24, 0x00, 0x00, 0x00, // chunk size
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x66, 0x6d, 0x74, 0x20, // fmt_
+            0x10, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x00, // audio format
+            0x00, 0x20, // num channels
+            0x80, 0xbb, 0x00, 0x00, // sample rate
+            0x00, 0x00, 0xe0, 0x2e, // byte rate
+            0x00, 0x40, // block align
+            0x10, 0x00, // bits per sample
+            0x64, 0x61, 0x74, 0x61, // data
+            0x00, 0x00, 0x00, 0x00, // chunk size
+        ];
+
+        let wav = Wav::from_bytes_with_options(&bytes, &ParseOptions::strict())
+            .unwrap()
+            .0;
+
+        assert_eq!(wav.fmt.num_channels, 0x2000);
+        assert!(wav.data.is_empty());
+    }
+
+    #[test]
+    fn ms_adpcm_is_written_as_pcm() {
+        let bytes: [u8; 72] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x40, 0x00, 0x00, 0x00, // chunk size
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x66, 0x6d, 0x74, 0x20, // fmt_
+            0x16, 0x00, 0x00, 0x00, // chunk size
+            0x02, 0x00, // audio format
+            0x01, 0x00, // num channels
+            0x40, 0x1f, 0x00, 0x00, // sample rate
+            0x40, 0x1f, 0x00, 0x00, // byte rate
+            0x09, 0x00, // block align
+            0x04, 0x00, // bits per sample
+            0x04, 0x00, // extension size
+            0x06, 0x00, // samples per block
+            0x00, 0x00, // num coefficients, use the standard table
+            0x66, 0x61, 0x63, 0x74, // fact
+            0x04, 0x00, 0x00, 0x00, // chunk size
+            0x05, 0x00, 0x00, 0x00, // num frames
+            0x64, 0x61, 0x74, 0x61, // data
+            0x09, 0x00, 0x00, 0x00, // chunk size
+            0x00, // predictor index
+            0x10, 0x00, // delta
+            0x64, 0x00, // sample 1
+            0x32, 0x00, // sample 2
+            0x12, 0xf0, // nibbles
+            0x00, // padding byte
+        ];
+
+        let wav = Wav::from_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            wav.fmt.format,
+            AudioFormat::MsAdpcm {
+                samples_per_block: 6
+            }
+        );
+        assert_eq!(wav.data, Data::BitDepth16(vec![50, 100, 116, 148, 132]));
+
+        let written = Wav::from_bytes(&wav.to_bytes()).unwrap();
+
+        assert_eq!(written.fmt.format, AudioFormat::Pcm);
+        assert_eq!(written.fmt.bit_depth, 16);
+        assert_eq!(written.data, wav.data);
+    }
+
+    #[test]
+    fn recover_truncated_data() {
+        let bytes: [u8; 55] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x00, 0x10, 0x00, 0x00, // chunk size, beyond the end of the file
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x66, 0x6d, 0x74, 0x20, // fmt_
+            0x10, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x00, // audio format
+            0x02, 0x00, // num channels
+            0x80, 0xbb, 0x00, 0x00, // sample rate
+            0x00, 0xee, 0x02, 0x00, // byte rate
+            0x04, 0x00, // block align
+            0x10, 0x00, // bits per sample
+            0x64, 0x61, 0x74, 0x61, // data
+            0x00, 0x0f, 0x00, 0x00, // chunk size, beyond the end of the file
+            0x01, 0x00, 0x02, 0x00, // sample 1 L+R
+            0x03, 0x00, 0x04, 0x00, // sample 2 L+R
+            0x05, 0x00, 0x06, // incomplete sample 3
+        ];
+
+        assert_eq!(
+            Wav::from_bytes(&bytes).unwrap_err(),
+            Error::CantParseSliceInto {
+                tag: ChunkTag::Data,
+                offset: 55
+            }
+        );
+
+        let (wav, warnings) =
+            Wav::from_bytes_with_options(&bytes, &ParseOptions::recover()).unwrap();
+
+        assert_eq!(wav.data, Data::BitDepth16(vec![1, 2, 3, 4]));
+        assert_eq!(
+            warnings,
+            [
+                ParseWarning {
+                    offset: 4,
+                    kind: ParseWarningKind::RiffSizeMismatch {
+                        declared: 0x1000,
+                        actual: 47
+                    }
+                },
+                ParseWarning {
+                    offset: 40,
+                    kind: ParseWarningKind::DataSizeRepaired {
+                        declared: 0x0f00,
+                        actual: 11
+                    }
+                },
+                ParseWarning {
+                    offset: 40,
+                    kind: ParseWarningKind::PartialFrame { trailing_bytes: 3 }
+                },
+            ]
+        );
+
+        let (repaired, _) = Wav::repair_bytes(&bytes).unwrap();
+
+        assert_eq!(repaired.len(), 52);
+        assert_eq!(repaired, wav.to_bytes());
+    }
+
+    #[test]
+    fn strict_mode_rejects_partial_frames() {
+        let bytes: [u8; 46] = [
+            0x52, 0x49, 0x46, 0x46, // RIFF
+            0x26, 0x00, 0x00, 0x00, // chunk size
+            0x57, 0x41, 0x56, 0x45, // WAVE
+            0x66, 0x6d, 0x74, 0x20, // fmt_
+            0x10, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x00, // audio format
+            0x02, 0x00, // num channels
+            0x80, 0xbb, 0x00, 0x00, // sample rate
+            0x00, 0xee, 0x02, 0x00, // byte rate
+            0x04, 0x00, // block align
+            0x10, 0x00, // bits per sample
+            0x64, 0x61, 0x74, 0x61, // data
+            0x02, 0x00, 0x00, 0x00, // chunk size
+            0x01, 0x00, // sample 1 L
+        ];
+
+        assert_eq!(
+            Wav::from_bytes_with_options(&bytes, &ParseOptions::strict()).unwrap_err(),
+            Error::Inconsistent(ParseWarning {
+                offset: 40,
+                kind: ParseWarningKind::PartialFrame { trailing_bytes: 2 }
+            })
+        );
+    }
 }
@@ -0,0 +1,76 @@
+/// Options for [`Wav::to_bytes_with_options`](crate::Wav::to_bytes_with_options)
+///
+/// Space is reserved with a single `JUNK` chunk right after the RIFF header, where it can
+/// later be replaced by an RF64 `ds64` chunk or taken up by more metadata.
+///
+/// ```
+/// use wavv::WriteOptions;
+///
+/// // Room for a `ds64` chunk, sample data starting at a multiple of 4096 bytes
+/// let options = WriteOptions {
+///     reserve: 28,
+///     align: 4096,
+/// };
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct WriteOptions {
+    /// Minimum number of bytes to reserve in the `JUNK` chunk, `0` for no reservation
+    pub reserve: usize,
+    /// Boundary the first sample byte is aligned to, `0` or `1` for no alignment
+    pub align: usize,
+}
+
+impl WriteOptions {
+    /// Total size of the `JUNK` chunk including its header, `0` if none is needed.
+    ///
+    /// `data_offset` is the offset the `data` chunk header would have without filler.
+    pub(crate) fn junk_size(&self, data_offset: usize) -> usize {
+        let align = self.align.max(1);
+        let mut size = match self.reserve {
+            0 => 0,
+            reserve => 8 + reserve + (reserve & 1),
+        };
+
+        // Chunks stay at even offsets and a filler chunk needs at least its own header
+        while !(data_offset + size + 8).is_multiple_of(align)
+            || !size.is_multiple_of(2)
+            || (size != 0 && size < 8)
+        {
+            size += 1;
+        }
+
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn junk_size() {
+        assert_eq!(WriteOptions::default().junk_size(36), 0);
+
+        let options = WriteOptions {
+            reserve: 3,
+            align: 0,
+        };
+
+        assert_eq!(options.junk_size(36), 12);
+
+        let options = WriteOptions {
+            reserve: 0,
+            align: 4096,
+        };
+
+        assert_eq!(options.junk_size(36), 4096 - 44);
+
+        // The gap is too small for a chunk header, skip to the next boundary
+        let options = WriteOptions {
+            reserve: 0,
+            align: 16,
+        };
+
+        assert_eq!(options.junk_size(36), 20);
+    }
+}